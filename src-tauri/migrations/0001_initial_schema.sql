@@ -0,0 +1,36 @@
+CREATE TABLE IF NOT EXISTS Users (
+    user_type INTEGER NOT NULL,
+    password TEXT NOT NULL,
+    user_name TEXT PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS Banners (
+    image_binary BLOB NOT NULL,
+    title TEXT,
+    release_day TEXT NOT NULL,
+    release_time TEXT NOT NULL,
+    current_episodes INTEGER NOT NULL,
+    total_episodes INTEGER NOT NULL,
+    user_name TEXT REFERENCES Users(user_name),
+    PRIMARY KEY (user_name, title)
+);
+
+CREATE TABLE IF NOT EXISTS Logs (
+    user_name TEXT REFERENCES Users(user_name),
+    log_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    action TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS SuspiciousUsers (
+    user_name TEXT REFERENCES Users(user_name),
+    PRIMARY KEY (user_name)
+);
+
+CREATE TABLE IF NOT EXISTS Sessions (
+    token TEXT PRIMARY KEY,
+    user_name TEXT NOT NULL REFERENCES Users(user_name),
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    revoked INTEGER NOT NULL DEFAULT 0
+);
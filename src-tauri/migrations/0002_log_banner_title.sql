@@ -0,0 +1 @@
+ALTER TABLE Logs ADD COLUMN banner_title TEXT;
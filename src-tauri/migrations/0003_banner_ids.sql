@@ -0,0 +1,35 @@
+CREATE TABLE Banners_new (
+    banner_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_binary BLOB NOT NULL,
+    title TEXT NOT NULL,
+    release_day TEXT NOT NULL,
+    release_time TEXT NOT NULL,
+    current_episodes INTEGER NOT NULL,
+    total_episodes INTEGER NOT NULL,
+    user_name TEXT REFERENCES Users(user_name),
+    UNIQUE (user_name, title)
+);
+
+INSERT INTO Banners_new (
+    image_binary,
+    title,
+    release_day,
+    release_time,
+    current_episodes,
+    total_episodes,
+    user_name
+)
+SELECT
+    image_binary,
+    -- titles were nullable, and more than one NULL could share a user
+    COALESCE(title, 'untitled-' || rowid),
+    release_day,
+    release_time,
+    current_episodes,
+    total_episodes,
+    user_name
+FROM Banners;
+
+DROP TABLE Banners;
+
+ALTER TABLE Banners_new RENAME TO Banners;
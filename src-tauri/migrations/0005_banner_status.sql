@@ -0,0 +1,5 @@
+ALTER TABLE Banners ADD COLUMN status TEXT NOT NULL DEFAULT 'Watching';
+
+UPDATE Banners
+SET status = 'Completed'
+WHERE total_episodes > 0 AND current_episodes >= total_episodes;
@@ -0,0 +1,8 @@
+CREATE TABLE Images (
+    image_hash TEXT PRIMARY KEY,
+    image_binary BLOB NOT NULL
+);
+
+ALTER TABLE Banners ADD COLUMN image_hash TEXT REFERENCES Images(image_hash);
+
+CREATE INDEX Banners_image_hash ON Banners(image_hash);
@@ -0,0 +1 @@
+ALTER TABLE Images ADD COLUMN thumbnail_binary BLOB;
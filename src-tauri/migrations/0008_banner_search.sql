@@ -0,0 +1,30 @@
+ALTER TABLE Banners ADD COLUMN alternate_titles TEXT NOT NULL DEFAULT '';
+ALTER TABLE Banners ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+
+CREATE VIRTUAL TABLE BannerSearch USING fts5(
+    title,
+    alternate_titles,
+    notes,
+    content = 'Banners',
+    content_rowid = 'banner_id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO BannerSearch (BannerSearch) VALUES ('rebuild');
+
+CREATE TRIGGER Banners_search_insert AFTER INSERT ON Banners BEGIN
+    INSERT INTO BannerSearch (rowid, title, alternate_titles, notes)
+    VALUES (new.banner_id, new.title, new.alternate_titles, new.notes);
+END;
+
+CREATE TRIGGER Banners_search_delete AFTER DELETE ON Banners BEGIN
+    INSERT INTO BannerSearch (BannerSearch, rowid, title, alternate_titles, notes)
+    VALUES ('delete', old.banner_id, old.title, old.alternate_titles, old.notes);
+END;
+
+CREATE TRIGGER Banners_search_update AFTER UPDATE OF title, alternate_titles, notes ON Banners BEGIN
+    INSERT INTO BannerSearch (BannerSearch, rowid, title, alternate_titles, notes)
+    VALUES ('delete', old.banner_id, old.title, old.alternate_titles, old.notes);
+    INSERT INTO BannerSearch (rowid, title, alternate_titles, notes)
+    VALUES (new.banner_id, new.title, new.alternate_titles, new.notes);
+END;
@@ -0,0 +1,7 @@
+CREATE TABLE BannerTags (
+    banner_id INTEGER NOT NULL REFERENCES Banners (banner_id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (banner_id, tag)
+);
+
+CREATE INDEX BannerTags_tag ON BannerTags (tag);
@@ -0,0 +1,3 @@
+-- existing banners are left without a time zone, since the one they were entered in is
+-- not known here; the app fills in the user's own zone when they next log in
+ALTER TABLE Banners ADD COLUMN time_zone TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,3 @@
+ALTER TABLE Banners ADD COLUMN mal_id INTEGER;
+
+CREATE UNIQUE INDEX Banners_mal_id ON Banners(user_name, mal_id);
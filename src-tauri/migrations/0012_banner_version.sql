@@ -0,0 +1,13 @@
+ALTER TABLE Banners ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE TRIGGER Banners_version_update AFTER UPDATE ON Banners WHEN new.version = old.version BEGIN
+    UPDATE Banners SET version = old.version + 1 WHERE banner_id = new.banner_id;
+END;
+
+CREATE TRIGGER BannerTags_version_insert AFTER INSERT ON BannerTags BEGIN
+    UPDATE Banners SET version = version + 1 WHERE banner_id = new.banner_id;
+END;
+
+CREATE TRIGGER BannerTags_version_delete AFTER DELETE ON BannerTags BEGIN
+    UPDATE Banners SET version = version + 1 WHERE banner_id = old.banner_id;
+END;
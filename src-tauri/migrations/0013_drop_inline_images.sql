@@ -0,0 +1,2 @@
+-- the app copies every inline cover into Images before this runs, see run_migrations
+ALTER TABLE Banners DROP COLUMN image_binary;
@@ -0,0 +1,18 @@
+-- banners saved before release days and times were validated may be written loosely;
+-- the ones that can still be read are rewritten, and the rest are left for the listings
+-- to skip and report
+UPDATE Banners
+SET release_day = upper(substr(trim(release_day), 1, 1)) || lower(substr(trim(release_day), 2))
+WHERE lower(trim(release_day))
+        IN ('sunday', 'monday', 'tuesday', 'wednesday', 'thursday', 'friday', 'saturday')
+    AND release_day
+        NOT IN ('Sunday', 'Monday', 'Tuesday', 'Wednesday', 'Thursday', 'Friday', 'Saturday');
+
+-- 9:30 becomes 09:30, and seconds are dropped from 09:30:00
+UPDATE Banners SET release_time = '0' || trim(release_time)
+WHERE trim(release_time) GLOB '[0-9]:[0-5][0-9]';
+
+UPDATE Banners SET release_time = substr(trim(release_time), 1, 5)
+WHERE (trim(release_time) GLOB '[0-2][0-9]:[0-5][0-9]'
+        OR trim(release_time) GLOB '[0-2][0-9]:[0-5][0-9]:[0-5][0-9]')
+    AND release_time <> substr(trim(release_time), 1, 5);
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom, Write};
+use zip::{result::ZipError, write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::banner::Banner;
+use crate::image_store::image_hash;
+use crate::password::is_password_hash;
+use crate::repo_error::RepoError;
+use crate::validation::{normalize_tags, validate_banner, MAX_IMPORT_BYTES};
+
+// bumped whenever the manifest layout changes, restores refuse any other version
+pub const BACKUP_VERSION: u32 = 2;
+
+// backups carry every image, so they may be far larger than an import file
+pub const MAX_BACKUP_BYTES: usize = 50 * MAX_IMPORT_BYTES;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const IMAGE_DIR: &str = "images";
+const THUMBNAIL_DIR: &str = "thumbnails";
+
+// the manifest holds password hashes, so only admins may create or restore a backup
+#[derive(Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: String,
+    pub users: Vec<BackupUser>,
+    pub banners: Vec<BackupBanner>,
+    pub logs: Vec<BackupLog>,
+    pub images: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct BackupUser {
+    pub user_name: String,
+    pub user_type: u32,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupBanner {
+    #[serde(flatten)]
+    pub banner: Banner,
+    pub user_name: String,
+    pub tags: Vec<String>,
+}
+
+// a banner row read with its owner, so the two cannot be paired up wrong
+#[derive(FromRow)]
+pub struct OwnedBanner {
+    #[sqlx(flatten)]
+    pub banner: Banner,
+    pub user_name: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct BackupLog {
+    pub user_name: Option<String>,
+    pub action: String,
+    pub banner_title: Option<String>,
+    pub details: Option<String>,
+    pub timestamp: String,
+}
+
+// how a restore treats users and banners that already exist
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum MergePolicy {
+    #[default]
+    KeepExisting,
+    PreferBackup,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct RestoreReport {
+    pub users_added: usize,
+    pub users_updated: usize,
+    pub users_skipped: usize,
+    pub banners_added: usize,
+    pub banners_updated: usize,
+    pub banners_skipped: usize,
+    pub logs_added: usize,
+}
+
+pub struct BackupImage {
+    pub binary: Vec<u8>,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+pub struct BackupWriter<W: Write + Seek> {
+    archive: ZipWriter<W>,
+}
+
+impl<W: Write + Seek> BackupWriter<W> {
+    // the manifest goes first, images follow one at a time so they never all sit in memory
+    pub fn new(writer: W, manifest: &BackupManifest) -> Result<Self, RepoError> {
+        let mut archive = ZipWriter::new(writer);
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        archive
+            .start_file(MANIFEST_FILE, deflated)
+            .map_err(write_failed)?;
+        archive
+            .write_all(&serde_json::to_vec_pretty(manifest).unwrap())
+            .map_err(|error| RepoError::database(error.to_string()))?;
+
+        Ok(BackupWriter { archive })
+    }
+
+    pub fn add_image(&mut self, hash: &str, image: &BackupImage) -> Result<(), RepoError> {
+        // covers are already compressed
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+        self.archive
+            .start_file(format!("{IMAGE_DIR}/{hash}"), stored)
+            .map_err(write_failed)?;
+        self.archive
+            .write_all(&image.binary)
+            .map_err(|error| RepoError::database(error.to_string()))?;
+
+        if let Some(thumbnail) = &image.thumbnail {
+            self.archive
+                .start_file(format!("{THUMBNAIL_DIR}/{hash}"), stored)
+                .map_err(write_failed)?;
+            self.archive
+                .write_all(thumbnail)
+                .map_err(|error| RepoError::database(error.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<W, RepoError> {
+        self.archive.finish().map_err(write_failed)
+    }
+}
+
+fn write_failed(error: ZipError) -> RepoError {
+    RepoError::database(error.to_string())
+}
+
+pub struct BackupReader<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    // shared by every entry, so many small files cannot add up past the limit either
+    budget: usize,
+}
+
+impl<R: Read + Seek> BackupReader<R> {
+    // reads and checks the manifest, images are checked as they are read
+    pub fn open(mut reader: R) -> Result<(Self, BackupManifest), RepoError> {
+        let invalid = |reason: &str| RepoError::validation("archive", reason);
+
+        let size = reader
+            .seek(SeekFrom::End(0))
+            .and_then(|size| reader.rewind().map(|_| size))
+            .map_err(|error| RepoError::validation("archive", error.to_string()))?;
+        if size > MAX_BACKUP_BYTES as u64 {
+            return Err(RepoError::validation(
+                "archive",
+                format!("must not exceed {MAX_BACKUP_BYTES} bytes"),
+            ));
+        }
+
+        let mut backup = BackupReader {
+            archive: ZipArchive::new(reader).map_err(|_| invalid("is not a zip archive"))?,
+            budget: MAX_BACKUP_BYTES,
+        };
+
+        let manifest = backup
+            .read_entry(MANIFEST_FILE)?
+            .ok_or_else(|| invalid("has no manifest.json"))?;
+        let manifest: BackupManifest = serde_json::from_slice(&manifest)
+            .map_err(|error| RepoError::validation("manifest", error.to_string()))?;
+
+        validate_manifest(&manifest)?;
+
+        Ok((backup, manifest))
+    }
+
+    pub fn read_image(&mut self, hash: &str) -> Result<BackupImage, RepoError> {
+        let binary = self
+            .read_entry(&format!("{IMAGE_DIR}/{hash}"))?
+            .ok_or_else(|| RepoError::validation("archive", format!("is missing image {hash}")))?;
+
+        if image_hash(&binary) != hash {
+            return Err(RepoError::validation(
+                "archive",
+                format!("image {hash} does not match its hash"),
+            ));
+        }
+
+        let thumbnail = self.read_entry(&format!("{THUMBNAIL_DIR}/{hash}"))?;
+        Ok(BackupImage { binary, thumbnail })
+    }
+
+    fn read_entry(&mut self, name: &str) -> Result<Option<Vec<u8>>, RepoError> {
+        let entry = match self.archive.by_name(name) {
+            Ok(entry) => entry,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(error) => return Err(RepoError::validation("archive", error.to_string())),
+        };
+
+        // the declared size cannot be trusted, so reading stops past the budget either way
+        let mut binary = vec![];
+        entry
+            .take(self.budget as u64 + 1)
+            .read_to_end(&mut binary)
+            .map_err(|error| RepoError::validation("archive", error.to_string()))?;
+
+        if binary.len() > self.budget {
+            return Err(RepoError::validation(
+                "archive",
+                format!("must not expand past {MAX_BACKUP_BYTES} bytes"),
+            ));
+        }
+
+        self.budget -= binary.len();
+        Ok(Some(binary))
+    }
+}
+
+fn validate_manifest(manifest: &BackupManifest) -> Result<(), RepoError> {
+    let invalid = |reason: String| RepoError::validation("manifest", reason);
+
+    if manifest.version != BACKUP_VERSION {
+        return Err(invalid(format!(
+            "version {} is not supported, expected {BACKUP_VERSION}",
+            manifest.version
+        )));
+    }
+
+    let mut user_names = HashSet::new();
+    for user in &manifest.users {
+        if user.user_type > 1 || !is_password_hash(&user.password) {
+            return Err(invalid(format!("user {:?} is not valid", user.user_name)));
+        }
+
+        if !user_names.insert(user.user_name.as_str()) {
+            return Err(invalid(format!(
+                "user {:?} is listed twice",
+                user.user_name
+            )));
+        }
+    }
+
+    let images: HashSet<&str> = manifest.images.iter().map(String::as_str).collect();
+    for entry in &manifest.banners {
+        if !user_names.contains(entry.user_name.as_str()) {
+            return Err(invalid(format!(
+                "banner {:?} belongs to unknown user {:?}",
+                entry.banner.title, entry.user_name
+            )));
+        }
+
+        if let Some(image_hash) = &entry.banner.image_hash {
+            if !images.contains(image_hash.as_str()) {
+                return Err(invalid(format!(
+                    "banner {:?} uses missing image {image_hash}",
+                    entry.banner.title
+                )));
+            }
+        }
+
+        validate_banner(&entry.banner)?;
+        normalize_tags(&entry.tags)?;
+    }
+
+    Ok(())
+}
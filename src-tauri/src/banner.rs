@@ -1,12 +1,171 @@
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Clone, FromRow)]
+use crate::repo_error::RepoError;
+use crate::schedule::DEFAULT_TIME_ZONE;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug, sqlx::Type)]
+pub enum WatchStatus {
+    #[default]
+    Watching,
+    Completed,
+    OnHold,
+    Dropped,
+    PlanToWatch,
+}
+
+impl WatchStatus {
+    pub const ALL: [WatchStatus; 5] = [
+        WatchStatus::Watching,
+        WatchStatus::Completed,
+        WatchStatus::OnHold,
+        WatchStatus::Dropped,
+        WatchStatus::PlanToWatch,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchStatus::Watching => "Watching",
+            WatchStatus::Completed => "Completed",
+            WatchStatus::OnHold => "OnHold",
+            WatchStatus::Dropped => "Dropped",
+            WatchStatus::PlanToWatch => "PlanToWatch",
+        }
+    }
+
+    // watching every episode of a known total completes a banner, whatever its status was
+    pub fn for_episodes(self, current_episodes: u32, total_episodes: u32) -> WatchStatus {
+        if total_episodes > 0 && current_episodes == total_episodes {
+            return WatchStatus::Completed;
+        }
+
+        self
+    }
+}
+
+impl FromStr for WatchStatus {
+    type Err = RepoError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        WatchStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .ok_or_else(|| RepoError::validation("status", format!("{value:?} is not a status")))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, FromRow)]
 pub struct Banner {
+    #[serde(default)]
+    pub banner_id: i64,
+    #[serde(default, skip_serializing)]
+    #[sqlx(default)]
     pub image_binary: Vec<u8>,
+    #[serde(default)]
+    pub image_hash: Option<String>,
     pub title: String,
+    #[serde(default)]
+    pub alternate_titles: String,
+    #[serde(default)]
+    pub notes: String,
     pub release_day: String,
     pub release_time: String,
+    #[serde(default = "default_time_zone")]
+    pub time_zone: String,
     pub current_episodes: u32,
     pub total_episodes: u32,
+    #[serde(default)]
+    pub status: WatchStatus,
+    #[serde(default)]
+    pub mal_id: Option<i64>,
+    // bumped by a trigger on every write, so offline edits can tell they are stale
+    #[serde(default)]
+    pub version: i64,
+}
+
+fn default_time_zone() -> String {
+    String::from(DEFAULT_TIME_ZONE)
+}
+
+#[derive(Deserialize, Default)]
+pub struct BannerPatch {
+    pub image_binary: Option<Vec<u8>>,
+    pub alternate_titles: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub release_day: Option<String>,
+    pub release_time: Option<String>,
+    pub time_zone: Option<String>,
+    pub current_episodes: Option<u32>,
+    pub total_episodes: Option<u32>,
+}
+
+impl BannerPatch {
+    pub fn describe_changes(&self, banner: &Banner) -> Vec<String> {
+        let mut changes = vec![];
+
+        if let Some(alternate_titles) = &self.alternate_titles {
+            if *alternate_titles != banner.alternate_titles {
+                changes.push(format!(
+                    "alternate_titles: {:?} -> {:?}",
+                    banner.alternate_titles, alternate_titles
+                ));
+            }
+        }
+
+        // notes can be long, so the log only records that they changed
+        if let Some(notes) = &self.notes {
+            if *notes != banner.notes {
+                changes.push(String::from("notes changed"));
+            }
+        }
+
+        if let Some(release_day) = &self.release_day {
+            if *release_day != banner.release_day {
+                changes.push(format!(
+                    "release_day: {:?} -> {:?}",
+                    banner.release_day, release_day
+                ));
+            }
+        }
+
+        if let Some(release_time) = &self.release_time {
+            if *release_time != banner.release_time {
+                changes.push(format!(
+                    "release_time: {:?} -> {:?}",
+                    banner.release_time, release_time
+                ));
+            }
+        }
+
+        if let Some(time_zone) = &self.time_zone {
+            if *time_zone != banner.time_zone {
+                changes.push(format!(
+                    "time_zone: {:?} -> {:?}",
+                    banner.time_zone, time_zone
+                ));
+            }
+        }
+
+        if let Some(current_episodes) = self.current_episodes {
+            if current_episodes != banner.current_episodes {
+                changes.push(format!(
+                    "current_episodes: {} -> {}",
+                    banner.current_episodes, current_episodes
+                ));
+            }
+        }
+
+        if let Some(total_episodes) = self.total_episodes {
+            if total_episodes != banner.total_episodes {
+                changes.push(format!(
+                    "total_episodes: {} -> {}",
+                    banner.total_episodes, total_episodes
+                ));
+            }
+        }
+
+        changes
+    }
 }
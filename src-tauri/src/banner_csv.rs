@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use crate::banner::Banner;
+use crate::banner_import::ImportIssue;
+use crate::repo_error::RepoError;
+use crate::schedule::DEFAULT_TIME_ZONE;
+use crate::validation::MAX_IMPORT_BYTES;
+
+// every Banners column except image_binary, in export order
+const EXPORT_HEADER: [&str; 12] = [
+    "banner_id",
+    "image_hash",
+    "title",
+    "alternate_titles",
+    "notes",
+    "release_day",
+    "release_time",
+    "time_zone",
+    "current_episodes",
+    "total_episodes",
+    "status",
+    "mal_id",
+];
+
+// the columns an import can set; image_hash is exported for reference only
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Column {
+    BannerId,
+    Title,
+    AlternateTitles,
+    Notes,
+    ReleaseDay,
+    ReleaseTime,
+    TimeZone,
+    CurrentEpisodes,
+    TotalEpisodes,
+    Status,
+    MalId,
+}
+
+impl Column {
+    pub const ALL: [Column; 11] = [
+        Column::BannerId,
+        Column::Title,
+        Column::AlternateTitles,
+        Column::Notes,
+        Column::ReleaseDay,
+        Column::ReleaseTime,
+        Column::TimeZone,
+        Column::CurrentEpisodes,
+        Column::TotalEpisodes,
+        Column::Status,
+        Column::MalId,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Column::BannerId => "banner_id",
+            Column::Title => "title",
+            Column::AlternateTitles => "alternate_titles",
+            Column::Notes => "notes",
+            Column::ReleaseDay => "release_day",
+            Column::ReleaseTime => "release_time",
+            Column::TimeZone => "time_zone",
+            Column::CurrentEpisodes => "current_episodes",
+            Column::TotalEpisodes => "total_episodes",
+            Column::Status => "status",
+            Column::MalId => "mal_id",
+        }
+    }
+
+    fn find(name: &str) -> Option<Column> {
+        let name = name.trim();
+
+        Column::ALL
+            .into_iter()
+            .find(|column| column.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+pub struct CsvRow {
+    pub row: usize,
+    values: Vec<(Column, String)>,
+}
+
+impl CsvRow {
+    fn value(&self, column: Column) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(c, _)| *c == column)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.value(Column::Title)
+    }
+
+    // an empty banner_id cell means the row has no id, like a missing column
+    pub fn banner_id(&self) -> Result<Option<i64>, RepoError> {
+        match self.value(Column::BannerId).map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(banner_id) => banner_id
+                .parse()
+                .map(Some)
+                .map_err(|_| RepoError::validation("banner_id", "must be a whole number")),
+        }
+    }
+
+    pub fn issue(&self, error: RepoError) -> ImportIssue {
+        ImportIssue {
+            row: self.row,
+            title: self.title().unwrap_or_default().to_owned(),
+            error,
+        }
+    }
+
+    // only the columns present in the file are written, so partial sheets keep the rest;
+    // empty cells also keep the current value unless the column may be blank
+    pub fn apply(&self, banner: &mut Banner) -> Result<(), RepoError> {
+        for (column, value) in &self.values {
+            let value = value.trim();
+
+            let may_be_blank = matches!(
+                column,
+                Column::AlternateTitles | Column::Notes | Column::MalId
+            );
+            if value.is_empty() && !may_be_blank {
+                continue;
+            }
+
+            match column {
+                Column::BannerId => {}
+                Column::Title => banner.title = value.to_owned(),
+                Column::AlternateTitles => banner.alternate_titles = value.to_owned(),
+                Column::Notes => banner.notes = value.to_owned(),
+                Column::ReleaseDay => banner.release_day = value.to_owned(),
+                Column::ReleaseTime => banner.release_time = value.to_owned(),
+                Column::TimeZone => banner.time_zone = value.to_owned(),
+                Column::CurrentEpisodes => {
+                    banner.current_episodes = parse_number(Column::CurrentEpisodes, value)?
+                }
+                Column::TotalEpisodes => {
+                    banner.total_episodes = parse_number(Column::TotalEpisodes, value)?
+                }
+                Column::Status => banner.status = value.parse()?,
+                Column::MalId => {
+                    banner.mal_id = match value {
+                        "" => None,
+                        value => Some(parse_number(Column::MalId, value)?),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// the starting point for inserted rows, which have to supply the schedule themselves
+pub fn new_banner() -> Banner {
+    Banner {
+        banner_id: 0,
+        image_binary: vec![],
+        image_hash: None,
+        title: String::new(),
+        alternate_titles: String::new(),
+        notes: String::new(),
+        release_day: String::new(),
+        release_time: String::new(),
+        time_zone: String::from(DEFAULT_TIME_ZONE),
+        current_episodes: 0,
+        total_episodes: 0,
+        status: Default::default(),
+        mal_id: None,
+        version: 0,
+    }
+}
+
+// spreadsheets run a cell starting with one of these as a formula, so such cells are
+// exported behind a ' and lose it again on import
+const FORMULA_PREFIXES: [char; 4] = ['=', '+', '-', '@'];
+
+// cells that already start with quotes before a prefix get one more, so they survive the trip
+fn looks_like_formula(cell: &str) -> bool {
+    cell.trim_start_matches('\'').starts_with(FORMULA_PREFIXES)
+}
+
+fn escape_formula(cell: String) -> String {
+    if looks_like_formula(&cell) {
+        format!("'{cell}")
+    } else {
+        cell
+    }
+}
+
+fn unescape_formula(cell: &str) -> &str {
+    match cell.strip_prefix('\'') {
+        Some(rest) if looks_like_formula(rest) => rest,
+        _ => cell,
+    }
+}
+
+pub fn banners_to_csv(banners: &[Banner]) -> Result<String, RepoError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let failed = |error: csv::Error| RepoError::database(error.to_string());
+
+    writer.write_record(EXPORT_HEADER).map_err(failed)?;
+
+    for banner in banners {
+        let record = [
+            banner.banner_id.to_string(),
+            banner.image_hash.clone().unwrap_or_default(),
+            banner.title.clone(),
+            banner.alternate_titles.clone(),
+            banner.notes.clone(),
+            banner.release_day.clone(),
+            banner.release_time.clone(),
+            banner.time_zone.clone(),
+            banner.current_episodes.to_string(),
+            banner.total_episodes.to_string(),
+            banner.status.as_str().to_owned(),
+            banner
+                .mal_id
+                .map(|mal_id| mal_id.to_string())
+                .unwrap_or_default(),
+        ];
+
+        writer
+            .write_record(record.map(escape_formula))
+            .map_err(failed)?;
+    }
+
+    let csv = writer
+        .into_inner()
+        .map_err(|error| RepoError::database(error.to_string()))?;
+
+    Ok(String::from_utf8(csv).unwrap())
+}
+
+// `mapping` renames file headers to columns; headers it leaves out are matched by
+// name and any that match no column are ignored
+pub fn parse_csv(
+    data: &[u8],
+    mapping: &HashMap<String, String>,
+) -> Result<Vec<Result<CsvRow, ImportIssue>>, RepoError> {
+    if data.len() > MAX_IMPORT_BYTES {
+        return Err(RepoError::validation(
+            "file",
+            format!("must not exceed {MAX_IMPORT_BYTES} bytes"),
+        ));
+    }
+
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+
+    let headers = reader
+        .headers()
+        .map_err(|_| RepoError::validation("file", "must start with a header row"))?
+        .clone();
+
+    let mut columns = vec![];
+    for (index, header) in headers.iter().enumerate() {
+        let column = match mapping.get(header) {
+            Some(name) => Some(Column::find(name).ok_or_else(|| {
+                RepoError::validation("mapping", format!("{name:?} is not a column"))
+            })?),
+            None => Column::find(header),
+        };
+
+        if let Some(column) = column {
+            if columns.iter().any(|(_, c)| *c == column) {
+                return Err(RepoError::validation(
+                    "mapping",
+                    format!("more than one header maps to {}", column.as_str()),
+                ));
+            }
+
+            columns.push((index, column));
+        }
+    }
+
+    if !columns
+        .iter()
+        .any(|(_, column)| matches!(column, Column::BannerId | Column::Title))
+    {
+        return Err(RepoError::validation(
+            "file",
+            "needs a banner_id or title column",
+        ));
+    }
+
+    Ok(reader
+        .records()
+        .enumerate()
+        .map(|(index, record)| {
+            // the header is row 1
+            let row = index + 2;
+
+            let record = record.map_err(|error| ImportIssue {
+                row,
+                title: String::new(),
+                error: RepoError::validation("row", error.to_string()),
+            })?;
+
+            Ok(CsvRow {
+                row,
+                values: columns
+                    .iter()
+                    .filter_map(|(index, column)| {
+                        record
+                            .get(*index)
+                            .map(|value| (*column, unescape_formula(value).to_owned()))
+                    })
+                    .collect(),
+            })
+        })
+        .collect())
+}
+
+fn parse_number<T: std::str::FromStr>(column: Column, value: &str) -> Result<T, RepoError> {
+    value
+        .parse()
+        .map_err(|_| RepoError::validation(column.as_str(), "must be a whole number"))
+}
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::banner::Banner;
+use crate::repo_error::RepoError;
+
+// rows are numbered from 1 in the order the file lists them
+#[derive(Serialize, Debug)]
+pub struct ImportIssue {
+    pub row: usize,
+    pub title: String,
+    pub error: RepoError,
+}
+
+// a row that would take the title or MyAnimeList id of another banner, which is left untouched
+#[derive(Serialize, Debug)]
+pub struct ImportConflict {
+    pub row: usize,
+    pub title: String,
+    pub banner_id: i64,
+}
+
+// a dry run reports the same outcome without writing anything
+#[derive(Serialize, Default, Debug)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub inserted: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub conflicts: Vec<ImportConflict>,
+    pub invalid: Vec<ImportIssue>,
+}
+
+// existing banners keyed by title and by MyAnimeList id; titles are compared exactly,
+// like the UNIQUE (user_name, title) constraint that adding and renaming rely on
+#[derive(Default)]
+pub struct ExistingBanners {
+    titles: HashMap<String, i64>,
+    mal_ids: HashMap<i64, i64>,
+}
+
+impl ExistingBanners {
+    pub fn insert(&mut self, banner_id: i64, title: &str, mal_id: Option<i64>) {
+        self.titles.insert(title.to_owned(), banner_id);
+
+        if let Some(mal_id) = mal_id {
+            self.mal_ids.insert(mal_id, banner_id);
+        }
+    }
+
+    pub fn remove(&mut self, title: &str, mal_id: Option<i64>) {
+        self.titles.remove(title);
+
+        if let Some(mal_id) = mal_id {
+            self.mal_ids.remove(&mal_id);
+        }
+    }
+
+    pub fn find_title(&self, title: &str) -> Option<i64> {
+        self.titles.get(title.trim()).copied()
+    }
+
+    // the banner, other than `banner_id` itself, that already uses this title or id
+    pub fn conflict(&self, banner: &Banner, banner_id: Option<i64>) -> Option<i64> {
+        let by_mal_id = banner.mal_id.and_then(|mal_id| self.mal_ids.get(&mal_id));
+        let by_title = self.titles.get(&banner.title);
+
+        [by_mal_id, by_title]
+            .into_iter()
+            .flatten()
+            .copied()
+            .find(|other| Some(*other) != banner_id)
+    }
+}
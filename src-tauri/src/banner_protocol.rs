@@ -0,0 +1,96 @@
+use tauri::http::{header, Request, Response, StatusCode};
+
+use crate::banner_repo::BannerRepo;
+use crate::image_store::image_content_type;
+use crate::repo_error::RepoError;
+
+pub const BANNER_SCHEME: &str = "banner";
+
+const CACHE_CONTROL: &str = "private, no-cache";
+
+const THUMBNAIL_SEGMENT: &str = "thumbnail";
+
+// serves banner://localhost/<banner_id>[/thumbnail]?token=<session token>
+pub async fn handle_banner_request(
+    repo: &BannerRepo,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let path = request.uri().path().trim_matches('/');
+    let (banner_id, thumbnail) = match path.split_once('/') {
+        Some((banner_id, THUMBNAIL_SEGMENT)) => (banner_id, true),
+        Some(_) => return empty_response(StatusCode::NOT_FOUND),
+        None => (path, false),
+    };
+
+    let banner_id = match banner_id.parse::<i64>() {
+        Ok(banner_id) => banner_id,
+        Err(_) => return empty_response(StatusCode::BAD_REQUEST),
+    };
+
+    let token = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .unwrap_or_default()
+        .to_owned();
+
+    let image_hash = match repo.get_banner_image_hash(banner_id, token).await {
+        Ok(image_hash) => image_hash,
+        Err(error) => return error_response(error),
+    };
+
+    let etag = match thumbnail {
+        true => format!("\"{image_hash}-{THUMBNAIL_SEGMENT}\""),
+        false => format!("\"{image_hash}\""),
+    };
+    let cached = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+
+    if cached {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .header(header::CACHE_CONTROL, CACHE_CONTROL)
+            .body(vec![])
+            .unwrap();
+    }
+
+    let image = match thumbnail {
+        true => repo.get_thumbnail(&image_hash).await,
+        false => repo.get_image(&image_hash).await,
+    };
+
+    let image_binary = match image {
+        Ok(image_binary) => image_binary,
+        Err(error) => return error_response(error),
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            image_content_type(&image_binary).unwrap_or("application/octet-stream"),
+        )
+        .header(header::CONTENT_LENGTH, image_binary.len())
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, CACHE_CONTROL)
+        .body(image_binary)
+        .unwrap()
+}
+
+fn error_response(error: RepoError) -> Response<Vec<u8>> {
+    match error {
+        RepoError::NotFound => empty_response(StatusCode::NOT_FOUND),
+        RepoError::Unauthorized => empty_response(StatusCode::UNAUTHORIZED),
+        _ => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+fn empty_response(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder().status(status).body(vec![]).unwrap()
+}
@@ -0,0 +1,179 @@
+use serde::Deserialize;
+use sqlx::{QueryBuilder, Sqlite};
+
+use crate::banner::WatchStatus;
+use crate::page::SortDirection;
+use crate::repo_error::RepoError;
+use crate::schedule::AiringOrder;
+use crate::search::match_expression;
+use crate::validation::{normalize_tags, Weekday};
+
+const PROGRESS: &str = "(CASE WHEN total_episodes > 0 \
+    THEN CAST(current_episodes AS REAL) / total_episodes ELSE 0 END)";
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct BannerFilter {
+    pub title: Option<String>,
+    pub status: Option<WatchStatus>,
+    pub release_days: Vec<Weekday>,
+    pub min_progress: Option<f64>,
+    pub max_progress: Option<f64>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum SortField {
+    Title,
+    Progress,
+    EpisodesRemaining,
+    DateAdded,
+    ReleaseDay,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct SortKey {
+    pub field: SortField,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct BannerQuery {
+    pub filter: BannerFilter,
+    pub sort: Vec<SortKey>,
+}
+
+impl SortField {
+    // only these fixed expressions ever reach the ORDER BY clause
+    fn push_expression(&self, builder: &mut QueryBuilder<'_, Sqlite>, airing_order: &AiringOrder) {
+        match self {
+            // the same ASCII-only folding as COLLATE NOCASE, but comparable as a plain value
+            SortField::Title => builder.push("lower(title)"),
+            SortField::Progress => builder.push(PROGRESS),
+            SortField::EpisodesRemaining => builder.push("(total_episodes - current_episodes)"),
+            SortField::DateAdded => builder.push("banner_id"),
+            SortField::ReleaseDay => {
+                airing_order.push_minutes_until_airing(builder);
+                builder
+            }
+        };
+    }
+}
+
+impl BannerQuery {
+    // checks the query and normalizes its tags and title text before it is compiled
+    pub fn validate(mut self) -> Result<ValidQuery, RepoError> {
+        let filter = &mut self.filter;
+
+        for progress in [filter.min_progress, filter.max_progress]
+            .into_iter()
+            .flatten()
+        {
+            if !(0.0..=1.0).contains(&progress) {
+                return Err(RepoError::validation("progress", "must be between 0 and 1"));
+            }
+        }
+
+        if let (Some(min_progress), Some(max_progress)) = (filter.min_progress, filter.max_progress)
+        {
+            if min_progress > max_progress {
+                return Err(RepoError::validation(
+                    "progress",
+                    "min_progress must not exceed max_progress",
+                ));
+            }
+        }
+
+        filter.tags = normalize_tags(&filter.tags)?;
+        let match_expression = filter.title.as_deref().and_then(match_expression);
+
+        Ok(ValidQuery {
+            query: self,
+            match_expression,
+        })
+    }
+}
+
+pub struct ValidQuery {
+    query: BannerQuery,
+    match_expression: Option<String>,
+}
+
+impl ValidQuery {
+    pub fn order(&self) -> Vec<SortDirection> {
+        self.query.sort.iter().map(|key| key.direction).collect()
+    }
+
+    // pushes a listing for fetch_page with one sort column per sort key, in order()
+    pub fn push_listing<'a>(
+        &'a self,
+        builder: &mut QueryBuilder<'a, Sqlite>,
+        user_name: &'a str,
+        airing_order: &AiringOrder,
+    ) {
+        let filter = &self.query.filter;
+
+        builder.push("SELECT *");
+        for (index, key) in self.query.sort.iter().enumerate() {
+            builder.push(", ");
+            key.field.push_expression(builder, airing_order);
+            builder.push(format!(" AS sort_{index}"));
+        }
+        builder.push(" FROM Banners WHERE user_name = ");
+        builder.push_bind(user_name);
+
+        if let Some(match_expression) = &self.match_expression {
+            builder
+                .push(
+                    " AND banner_id IN \
+                    (SELECT rowid FROM BannerSearch WHERE BannerSearch MATCH ",
+                )
+                .push_bind(format!("{{title alternate_titles}} : ({match_expression})"))
+                .push(")");
+        }
+
+        if let Some(status) = filter.status {
+            builder.push(" AND status = ").push_bind(status);
+        }
+
+        if !filter.release_days.is_empty() {
+            builder.push(" AND release_day IN (");
+            let mut days = builder.separated(", ");
+            for day in &filter.release_days {
+                days.push_bind(day.as_str());
+            }
+            builder.push(")");
+        }
+
+        if let Some(min_progress) = filter.min_progress {
+            builder
+                .push(" AND ")
+                .push(PROGRESS)
+                .push(" >= ")
+                .push_bind(min_progress);
+        }
+
+        if let Some(max_progress) = filter.max_progress {
+            builder
+                .push(" AND ")
+                .push(PROGRESS)
+                .push(" <= ")
+                .push_bind(max_progress);
+        }
+
+        // a banner must carry every requested tag
+        if !filter.tags.is_empty() {
+            builder.push(
+                " AND (SELECT COUNT(*) FROM BannerTags \
+                WHERE BannerTags.banner_id = Banners.banner_id AND tag IN (",
+            );
+            let mut tags = builder.separated(", ");
+            for tag in &filter.tags {
+                tags.push_bind(tag.as_str());
+            }
+            builder.push(")) = ").push_bind(filter.tags.len() as i64);
+        }
+    }
+}
@@ -1,13 +1,48 @@
 use serde::Serialize;
-use sqlx::{Row, Sqlite};
+use sqlx::{Connection, FromRow, QueryBuilder, Row, Sqlite, Transaction};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Seek, Write};
+use std::path::PathBuf;
 
-use crate::banner::Banner;
+use crate::backup::{
+    BackupBanner, BackupImage, BackupLog, BackupManifest, BackupReader, BackupUser, BackupWriter,
+    MergePolicy, OwnedBanner, RestoreReport, BACKUP_VERSION,
+};
+use crate::banner::{Banner, BannerPatch, WatchStatus};
+use crate::banner_csv::{banners_to_csv, new_banner, parse_csv};
+use crate::banner_import::{ExistingBanners, ImportConflict, ImportIssue, ImportReport};
+use crate::banner_query::BannerQuery;
+use crate::ics::{banners_to_ics, IcsExport};
+use crate::image_store::{
+    image_hash, process_upload, remove_orphaned_images, store_image, ProcessedImage,
+};
+use crate::mal::{banners_to_mal_xml, parse_mal_export, MalExport};
+use crate::page::{fetch_page, paginate, Page, PageRequest, SortDirection};
+use crate::password::{dummy_password_hash, hash_password, verify_password};
+use crate::repo_error::RepoError;
+use crate::schedule::{next_airing, weekly_schedule, AiringOrder, NextAiring, WeeklySchedule};
+use crate::search::{
+    fuzzy_score, match_expression, normalize_for_search, SearchMode, SearchResult,
+    ALTERNATE_TITLES_WEIGHT, FUZZY_THRESHOLD, NOTES_WEIGHT, SNIPPET_ELLIPSIS, SNIPPET_END,
+    SNIPPET_START, SNIPPET_TOKENS, TITLE_WEIGHT,
+};
+use crate::session::{generate_token, SESSION_DURATION};
+use crate::snapshot::{
+    list_snapshots, restore_snapshot, rotate_snapshots, take_snapshot, write_snapshot,
+    SnapshotConfig, SnapshotInfo,
+};
+use crate::sync::{SyncChange, SyncOperation, SyncOutcome, SyncReport, MAX_SYNC_CHANGES};
+use crate::validation::{
+    normalize_tags, normalize_title, parse_date, parse_time_zone, validate_banner, validate_image,
+    validate_patch,
+};
 
 #[derive(Serialize)]
 #[serde(tag = "status")]
 pub enum LoginResult {
-    Admin,
-    User,
+    Admin { token: String },
+    User { token: String },
     Fail { error: String },
 }
 
@@ -22,46 +57,153 @@ impl BannerRepo {
 }
 
 impl BannerRepo {
-    pub async fn login(&self, user_name: String, password: String) -> Result<LoginResult, String> {
+    pub async fn login(
+        &self,
+        user_name: String,
+        password: String,
+    ) -> Result<LoginResult, RepoError> {
         let result = sqlx::query("SELECT user_type, password FROM Users WHERE user_name = ?")
-            .bind(user_name)
+            .bind(&user_name)
             .fetch_optional(&self.database)
-            .await
-            .map_err(|e| e.to_string())?;
+            .await?;
 
-        if let Some(row) = result {
-            let stored_password: String = row.get("password");
-            if password != stored_password {
-                return Ok(LoginResult::Fail {
-                    error: String::from("invalid password"),
-                });
-            }
+        // unknown users and wrong passwords fail alike, so neither the message nor the
+        // time taken tells which user names exist
+        let stored_hash: String = match &result {
+            Some(row) => row.get("password"),
+            None => String::from(dummy_password_hash()),
+        };
+        let verified = verify_password(&password, &stored_hash);
 
-            let user_type: u32 = row.get("user_type");
+        let Some(row) = result.filter(|_| verified) else {
+            return Ok(LoginResult::Fail {
+                error: String::from("invalid user name or password"),
+            });
+        };
 
-            return match user_type {
-                1 => Ok(LoginResult::User),
-                0 => Ok(LoginResult::Admin),
-                _ => Err(String::from("what")),
-            };
+        let user_type: u32 = row.get("user_type");
+        if user_type > 1 {
+            return Err(RepoError::database("unknown user type"));
         }
 
-        Ok(LoginResult::Fail {
-            error: String::from("user not found"),
-        })
+        let token = self.create_session(user_name).await?;
+
+        match user_type {
+            0 => Ok(LoginResult::Admin { token }),
+            _ => Ok(LoginResult::User { token }),
+        }
+    }
+
+    pub async fn logout(&self, token: String) -> Result<(), RepoError> {
+        sqlx::query("UPDATE Sessions SET revoked = 1 WHERE token = ?")
+            .bind(token)
+            .execute(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    // sessions that can no longer be resolved are cleared out whenever a new one starts
+    async fn create_session(&self, user_name: String) -> Result<String, RepoError> {
+        sqlx::query(
+            "DELETE FROM Sessions WHERE revoked = 1 OR julianday(expires_at) <= julianday('now')",
+        )
+        .execute(&self.database)
+        .await?;
+
+        let token = generate_token();
+        let now = time::OffsetDateTime::now_utc();
+        let created_at = now
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap();
+        let expires_at = (now + SESSION_DURATION)
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap();
+
+        sqlx::query(
+            r#"
+            INSERT INTO Sessions (
+                token,
+                user_name,
+                created_at,
+                expires_at
+            ) VALUES (?, ?, ?, ?)"#,
+        )
+        .bind(&token)
+        .bind(user_name)
+        .bind(created_at)
+        .bind(expires_at)
+        .execute(&self.database)
+        .await?;
+
+        Ok(token)
+    }
+
+    pub async fn resolve_session(&self, token: &str) -> Result<String, RepoError> {
+        let session: Option<(String, String, bool)> =
+            sqlx::query_as("SELECT user_name, expires_at, revoked FROM Sessions WHERE token = ?")
+                .bind(token)
+                .fetch_optional(&self.database)
+                .await?;
+
+        let (user_name, expires_at, revoked) = match session {
+            Some(session) => session,
+            None => return Err(RepoError::Unauthorized),
+        };
+
+        if revoked {
+            return Err(RepoError::Unauthorized);
+        }
+
+        let expires_at = time::OffsetDateTime::parse(
+            &expires_at,
+            &time::format_description::well_known::Rfc3339,
+        )
+        .map_err(|_| RepoError::Unauthorized)?;
+
+        if expires_at <= time::OffsetDateTime::now_utc() {
+            return Err(RepoError::Unauthorized);
+        }
+
+        Ok(user_name)
+    }
+
+    // backups and restores span every user, so they are limited to admins
+    async fn resolve_admin(&self, token: &str) -> Result<String, RepoError> {
+        let user_name = self.resolve_session(token).await?;
+
+        let user_type: u32 = sqlx::query_scalar("SELECT user_type FROM Users WHERE user_name = ?")
+            .bind(&user_name)
+            .fetch_one(&self.database)
+            .await?;
+
+        if user_type != 0 {
+            return Err(RepoError::Unauthorized);
+        }
+
+        Ok(user_name)
     }
 
+    // anyone may sign up, but only an admin may create another admin
     pub async fn register_user(
         &self,
         user_name: String,
         password: String,
         is_admin: bool,
-    ) -> Result<bool, String> {
+        token: Option<String>,
+    ) -> Result<(), RepoError> {
+        if is_admin {
+            self.resolve_admin(token.as_deref().unwrap_or_default())
+                .await?;
+        }
+
         let user_type = match is_admin {
             true => 0,
             false => 1,
         };
-        let result = sqlx::query(
+        let password_hash = hash_password(&password).map_err(RepoError::database)?;
+
+        sqlx::query(
             r#"
                 INSERT INTO Users (
                     user_type,
@@ -70,339 +212,2425 @@ impl BannerRepo {
                     ) VALUES (?, ?, ?)"#,
         )
         .bind(user_type)
-        .bind(password)
+        .bind(password_hash)
         .bind(user_name)
         .execute(&self.database)
-        .await;
+        .await?;
 
-        match result {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                if let Some(db_err) = e.as_database_error() {
-                    let code = db_err.code().map(|c| c.to_string()).unwrap_or_default();
+        Ok(())
+    }
 
-                    if code == "2067" {
-                        return Ok(false);
-                    }
-                }
+    pub async fn add_banner(&self, mut banner: Banner, token: String) -> Result<i64, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let image = prepare_new_banner(&mut banner).await?;
 
-                Err(e.to_string())
-            }
-        }
+        let mut transaction = self.database.begin().await?;
+        let banner_id = add_user_banner(&mut transaction, &user_name, &banner, image).await?;
+        transaction.commit().await?;
+
+        Ok(banner_id)
     }
 
-    pub async fn add_banner(&self, banner: Banner, user_name: String) -> Result<(), String> {
-        sqlx::query(
-            r#"
-            INSERT INTO Banners (
-                image_binary,
-                title,
-                release_day,
-                release_time,
-                current_episodes,
-                total_episodes,
-                user_name
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&banner.image_binary)
-        .bind(&banner.title)
-        .bind(&banner.release_day)
-        .bind(&banner.release_time)
-        .bind(banner.current_episodes as i64)
-        .bind(banner.total_episodes as i64)
-        .bind(user_name.clone())
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+    pub async fn delete_banner(&self, banner_id: i64, token: String) -> Result<(), RepoError> {
+        let user_name = self.resolve_session(&token).await?;
 
-        let timestamp = time::OffsetDateTime::now_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
+        let mut transaction = self.database.begin().await?;
+        delete_user_banner(&mut transaction, &user_name, banner_id).await?;
+        transaction.commit().await?;
 
-        sqlx::query(
-            r#"
-        INSERT INTO Logs (user_name, action, timestamp)
-        VALUES (?, ?, ?)
-        "#,
-        )
-        .bind(user_name)
-        .bind("add")
-        .bind(timestamp)
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub async fn rename_banner(
+        &self,
+        banner_id: i64,
+        title: String,
+        token: String,
+    ) -> Result<(), RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let title = normalize_title(&title)?;
+
+        let mut transaction = self.database.begin().await?;
+        rename_user_banner(&mut transaction, &user_name, banner_id, &title).await?;
+        transaction.commit().await?;
 
         Ok(())
     }
 
-    pub async fn delete_banner(&self, title: String, user_name: String) -> Result<(), String> {
-        sqlx::query(r#"DELETE FROM Banners WHERE title = ? AND user_name = ?"#)
-            .bind(title)
-            .bind(user_name.clone())
-            .execute(&self.database)
-            .await
-            .map_err(|e| e.to_string())?;
+    pub async fn get_banner_image(
+        &self,
+        banner_id: i64,
+        token: String,
+    ) -> Result<Vec<u8>, RepoError> {
+        let image_hash = self.get_banner_image_hash(banner_id, token).await?;
+        self.get_image(&image_hash).await
+    }
 
-        let timestamp = time::OffsetDateTime::now_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
+    pub async fn get_banner_image_hash(
+        &self,
+        banner_id: i64,
+        token: String,
+    ) -> Result<String, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
 
-        sqlx::query(
-            r#"
-        INSERT INTO Logs (user_name, action, timestamp)
-        VALUES (?, ?, ?)
-        "#,
+        let image_hash: Option<String> = sqlx::query_scalar(
+            r#"SELECT image_hash FROM Banners WHERE banner_id = ? AND user_name = ?"#,
         )
+        .bind(banner_id)
         .bind(user_name)
-        .bind("delete")
-        .bind(timestamp)
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        .fetch_optional(&self.database)
+        .await?
+        .ok_or(RepoError::NotFound)?;
 
-        Ok(())
+        image_hash.ok_or(RepoError::NotFound)
+    }
+
+    // images are content addressed, so callers must resolve the hash through a banner they own
+    pub async fn get_image(&self, image_hash: &str) -> Result<Vec<u8>, RepoError> {
+        sqlx::query_scalar(r#"SELECT image_binary FROM Images WHERE image_hash = ?"#)
+            .bind(image_hash)
+            .fetch_optional(&self.database)
+            .await?
+            .ok_or(RepoError::NotFound)
+    }
+
+    pub async fn get_banner_tags(
+        &self,
+        banner_id: i64,
+        token: String,
+    ) -> Result<Vec<String>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let mut transaction = self.database.begin().await?;
+
+        sqlx::query(r#"SELECT banner_id FROM Banners WHERE banner_id = ? AND user_name = ?"#)
+            .bind(banner_id)
+            .bind(&user_name)
+            .fetch_optional(&mut transaction)
+            .await?
+            .ok_or(RepoError::NotFound)?;
+
+        fetch_tags(&mut transaction, banner_id).await
+    }
+
+    // covers stored before thumbnails existed fall back to the full image
+    pub async fn get_thumbnail(&self, image_hash: &str) -> Result<Vec<u8>, RepoError> {
+        sqlx::query_scalar(
+            r#"SELECT COALESCE(thumbnail_binary, image_binary) FROM Images WHERE image_hash = ?"#,
+        )
+        .bind(image_hash)
+        .fetch_optional(&self.database)
+        .await?
+        .ok_or(RepoError::NotFound)
     }
 
     pub async fn search_banners(
         &self,
         query: String,
+        mode: SearchMode,
+        status: Option<WatchStatus>,
         page_size: usize,
-        page_count: usize,
-        user_name: String,
-    ) -> Result<Vec<Banner>, String> {
-        sqlx::query_as(
-            r#"
-        SELECT * FROM Banners
-        WHERE title LIKE ? AND user_name = ?
-        LIMIT ? OFFSET ?"#,
+        cursor: Option<String>,
+        token: String,
+    ) -> Result<Page<SearchResult>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let request = PageRequest::new(page_size, cursor)?;
+
+        match mode {
+            SearchMode::FullText => {
+                self.full_text_search(&query, status, &request, &user_name)
+                    .await
+            }
+            SearchMode::Fuzzy => {
+                self.fuzzy_search(&query, status, &request, &user_name)
+                    .await
+            }
+        }
+    }
+
+    async fn full_text_search(
+        &self,
+        query: &str,
+        status: Option<WatchStatus>,
+        request: &PageRequest,
+        user_name: &str,
+    ) -> Result<Page<SearchResult>, RepoError> {
+        let Some(match_expression) = match_expression(query) else {
+            return fetch_page(&self.database, request, &[], |builder| {
+                builder
+                    .push(
+                        r#"
+                SELECT *, '' AS snippet FROM Banners
+                WHERE user_name = "#,
+                    )
+                    .push_bind(user_name)
+                    .push(" AND (")
+                    .push_bind(status)
+                    .push(" IS NULL OR status = ")
+                    .push_bind(status)
+                    .push(")");
+            })
+            .await;
+        };
+
+        // a match is ranked by bm25, which depends on every row in the index, so a score
+        // cannot be resumed from in SQL; every match is ranked and paged by its score
+        let mut builder = QueryBuilder::new("SELECT Banners.*, snippet(BannerSearch, -1, ");
+        builder
+            .push_bind(SNIPPET_START)
+            .push(", ")
+            .push_bind(SNIPPET_END)
+            .push(", ")
+            .push_bind(SNIPPET_ELLIPSIS)
+            .push(", ")
+            .push_bind(SNIPPET_TOKENS)
+            .push(") AS snippet, bm25(BannerSearch, ")
+            .push_bind(TITLE_WEIGHT)
+            .push(", ")
+            .push_bind(ALTERNATE_TITLES_WEIGHT)
+            .push(", ")
+            .push_bind(NOTES_WEIGHT)
+            .push(
+                r#") AS rank
+            FROM BannerSearch
+            JOIN Banners ON Banners.banner_id = BannerSearch.rowid
+            WHERE BannerSearch MATCH "#,
+            )
+            .push_bind(match_expression.as_str())
+            .push(" AND Banners.user_name = ")
+            .push_bind(user_name)
+            .push(" AND (")
+            .push_bind(status)
+            .push(" IS NULL OR Banners.status = ")
+            .push_bind(status)
+            .push(")");
+
+        let ranked = builder
+            .build()
+            .fetch_all(&self.database)
+            .await?
+            .iter()
+            .map(|row| {
+                let result = SearchResult::from_row(row)?;
+                Ok((row.try_get("rank")?, result.banner.banner_id, result))
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+        paginate(ranked, request)
+    }
+
+    async fn fuzzy_search(
+        &self,
+        query: &str,
+        status: Option<WatchStatus>,
+        request: &PageRequest,
+        user_name: &str,
+    ) -> Result<Page<SearchResult>, RepoError> {
+        let query = normalize_for_search(query);
+        if query.is_empty() {
+            return self
+                .full_text_search(&query, status, request, user_name)
+                .await;
+        }
+
+        let banners: Vec<Banner> = sqlx::query_as(
+            r#"SELECT * FROM Banners WHERE user_name = ? AND (? IS NULL OR status = ?)"#,
         )
-        .bind(format!("%{}%", query))
         .bind(user_name)
-        .bind(page_size as i64)
-        .bind(page_count as i64 * page_size as i64)
+        .bind(status)
+        .bind(status)
         .fetch_all(&self.database)
-        .await
-        .map_err(|e| e.to_string())
+        .await?;
+
+        // higher scores sort first, so the sort key is the negated score
+        let scored = banners
+            .into_iter()
+            .filter_map(|banner| {
+                let (score, matched) = std::iter::once(banner.title.as_str())
+                    .chain(banner.alternate_titles.lines())
+                    .map(|candidate| {
+                        let score = fuzzy_score(&query, &normalize_for_search(candidate));
+                        (score, candidate.trim().to_owned())
+                    })
+                    .max_by(|a, b| a.0.total_cmp(&b.0))?;
+
+                (score >= FUZZY_THRESHOLD).then(|| {
+                    let banner_id = banner.banner_id;
+                    let result = SearchResult {
+                        banner,
+                        snippet: format!("{SNIPPET_START}{matched}{SNIPPET_END}"),
+                    };
+                    (-score, banner_id, result)
+                })
+            })
+            .collect();
+
+        paginate(scored, request)
     }
 
-    pub async fn get_all_banners(&self, user_name: String) -> Result<Vec<Banner>, String> {
+    pub async fn get_all_banners(&self, token: String) -> Result<Vec<Banner>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+
         sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ?"#)
             .bind(user_name)
             .fetch_all(&self.database)
             .await
-            .map_err(|e| e.to_string())
+            .map_err(RepoError::from)
     }
 
-    pub async fn update_banner_current_episodes(
+    pub async fn set_banner_status(
         &self,
-        title: String,
-        current_episodes: u32,
-        user_name: String,
-    ) -> Result<(), String> {
-        sqlx::query(
-            r#"
-        UPDATE Banners
-        SET current_episodes = ?
-        WHERE title = ? AND user_name = ?"#,
-        )
-        .bind(current_episodes)
-        .bind(title)
-        .bind(user_name.clone())
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
-
-        let timestamp = time::OffsetDateTime::now_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
+        banner_id: i64,
+        status: WatchStatus,
+        token: String,
+    ) -> Result<(), RepoError> {
+        let user_name = self.resolve_session(&token).await?;
 
-        sqlx::query(
-            r#"
-        INSERT INTO Logs (user_name, action, timestamp)
-        VALUES (?, ?, ?)
-        "#,
-        )
-        .bind(user_name)
-        .bind("update current episodes")
-        .bind(timestamp)
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        let mut transaction = self.database.begin().await?;
+        set_user_banner_status(&mut transaction, &user_name, banner_id, status).await?;
+        transaction.commit().await?;
 
         Ok(())
     }
 
-    pub async fn update_banner_total_episodes(
+    // banners from before time zones were stored have none, and are taken to be in the
+    // zone the user is in now
+    pub async fn fill_missing_time_zones(
         &self,
-        title: String,
-        total_episodes: u32,
-        user_name: String,
-    ) -> Result<(), String> {
-        sqlx::query(
-            r#"
-        UPDATE Banners
-        SET total_episodes = ?
-        WHERE title = ? AND user_name = ?"#,
-        )
-        .bind(total_episodes)
-        .bind(title)
-        .bind(user_name.clone())
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        time_zone: String,
+        token: String,
+    ) -> Result<u64, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        parse_time_zone(&time_zone)?;
 
-        let timestamp = time::OffsetDateTime::now_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
-
-        sqlx::query(
-            r#"
-        INSERT INTO Logs (user_name, action, timestamp)
-        VALUES (?, ?, ?)
-        "#,
+        let result = sqlx::query(
+            r#"UPDATE Banners SET time_zone = ? WHERE user_name = ? AND time_zone = ''"#,
         )
+        .bind(time_zone)
         .bind(user_name)
-        .bind("update total episodes")
-        .bind(timestamp)
         .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
-        Ok(())
+        Ok(result.rows_affected())
     }
 
-    pub async fn update_banner_release_day(
+    pub async fn update_banner(
         &self,
-        title: String,
-        release_day: String,
-        user_name: String,
-    ) -> Result<(), String> {
-        sqlx::query(
-            r#"
-        UPDATE Banners
-        SET release_day = ?
-        WHERE title = ? AND user_name = ?"#,
-        )
-        .bind(release_day)
-        .bind(title)
-        .bind(user_name.clone())
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
-
-        let timestamp = time::OffsetDateTime::now_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
+        banner_id: i64,
+        mut patch: BannerPatch,
+        token: String,
+    ) -> Result<(), RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let image = prepare_patch_image(&mut patch).await?;
 
-        sqlx::query(
-            r#"
-        INSERT INTO Logs (user_name, action, timestamp)
-        VALUES (?, ?, ?)
-        "#,
-        )
-        .bind(user_name)
-        .bind("update release day")
-        .bind(timestamp)
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        let mut transaction = self.database.begin().await?;
+        update_user_banner(&mut transaction, &user_name, banner_id, &patch, image).await?;
+        transaction.commit().await?;
 
         Ok(())
     }
 
-    pub async fn update_banner_release_time(
+    // applies a batch of offline edits in the order they were made, all in one transaction;
+    // an edit that fails or conflicts is skipped without undoing the others
+    pub async fn sync_changes(
         &self,
-        title: String,
-        release_time: String,
-        user_name: String,
-    ) -> Result<(), String> {
-        sqlx::query(
-            r#"
-        UPDATE Banners
-        SET release_time = ?
-        WHERE title = ? AND user_name = ?"#,
-        )
-        .bind(release_time)
-        .bind(title)
-        .bind(user_name.clone())
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        changes: Vec<SyncChange>,
+        token: String,
+    ) -> Result<SyncReport, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
 
-        let timestamp = time::OffsetDateTime::now_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
+        if changes.len() > MAX_SYNC_CHANGES {
+            return Err(RepoError::validation(
+                "changes",
+                format!("must not exceed {MAX_SYNC_CHANGES} entries"),
+            ));
+        }
 
-        sqlx::query(
-            r#"
-        INSERT INTO Logs (user_name, action, timestamp)
-        VALUES (?, ?, ?)
-        "#,
-        )
-        .bind(user_name)
-        .bind("update release time")
-        .bind(timestamp)
-        .execute(&self.database)
-        .await
-        .map_err(|e| e.to_string())?;
+        let mut outcomes: Vec<Option<SyncOutcome>> = changes.iter().map(|_| None).collect();
+        let mut queue = vec![];
+        for (index, mut change) in changes.into_iter().enumerate() {
+            let prepared = match change.parse_timestamp() {
+                Ok(timestamp) => prepare_sync_operation(&mut change.operation)
+                    .await
+                    .map(|image| (timestamp, image)),
+                Err(error) => Err(error),
+            };
 
-        Ok(())
+            match prepared {
+                Ok((timestamp, image)) => queue.push((timestamp, index, change, image)),
+                Err(error) => outcomes[index] = Some(SyncOutcome::Failed { error }),
+            }
+        }
+
+        // the queue on the client has no guaranteed order, ties keep the order they were sent in
+        queue.sort_by_key(|(timestamp, index, _, _)| (*timestamp, *index));
+
+        let mut transaction = self.database.begin().await?;
+        let mut batch = SyncBatch::default();
+
+        for (_, index, change, image) in queue {
+            let mut savepoint = transaction.begin().await?;
+
+            let outcome =
+                match apply_sync_change(&mut savepoint, &user_name, &batch, &change, image).await {
+                    Ok(outcome) => {
+                        savepoint.commit().await?;
+                        batch.record(&change, &outcome);
+                        outcome
+                    }
+                    Err(error) => {
+                        savepoint.rollback().await?;
+                        SyncOutcome::Failed { error }
+                    }
+                };
+
+            outcomes[index] = Some(outcome);
+        }
+
+        let banners = fetch_user_banners(&mut transaction, &user_name).await?;
+        transaction.commit().await?;
+
+        Ok(SyncReport {
+            outcomes: outcomes.into_iter().flatten().collect(),
+            banners,
+        })
     }
 
     pub async fn sort_banners_by_release_day(
         &self,
         page_size: usize,
-        page_count: usize,
-        user_name: String,
-    ) -> Result<Vec<Banner>, String> {
-        sqlx::query_as(
-            r#"
-            SELECT * FROM banners
-            WHERE user_name = ?
-            ORDER BY 
-            ( 
-                ( 
-                    (CASE release_day
-                        WHEN 'Sunday' THEN 0
-                        WHEN 'Monday' THEN 1
-                        WHEN 'Tuesday' THEN 2
-                        WHEN 'Wednesday' THEN 3
-                        WHEN 'Thursday' THEN 4
-                        WHEN 'Friday' THEN 5
-                        WHEN 'Saturday' THEN 6
-                        END
-                    ) 
-                    - strftime('%w', 'now') + 7
-                ) % 7
-            )
-            LIMIT ? OFFSET ?;"#,
+        cursor: Option<String>,
+        token: String,
+    ) -> Result<Page<Banner>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let request = PageRequest::new(page_size, cursor)?;
+        let airing_order = self.airing_order(&user_name, &request).await?;
+
+        fetch_page(
+            &self.database,
+            &request,
+            &[SortDirection::Ascending],
+            |builder| {
+                builder.push("SELECT *, ");
+                airing_order.push_minutes_until_airing(builder);
+                builder
+                    .push(" AS sort_0 FROM Banners WHERE user_name = ")
+                    .push_bind(user_name.as_str());
+            },
         )
-        .bind(user_name)
-        .bind(page_size as i64)
-        .bind(page_count as i64 * page_size as i64)
-        .fetch_all(&self.database)
         .await
-        .map_err(|e| e.to_string())
     }
 
-    pub async fn get_paged_banners(
+    // release days are counted from when the listing's first page was read
+    async fn airing_order(
         &self,
-        page_size: usize,
-        page_count: usize,
-        user_name: String,
-    ) -> Result<Vec<Banner>, String> {
-        sqlx::query_as(
-            r#"
-        SELECT * FROM Banners
-        WHERE user_name = ?
-        LIMIT ? OFFSET ?"#,
+        user_name: &str,
+        request: &PageRequest,
+    ) -> Result<AiringOrder, RepoError> {
+        let time_zones: Vec<String> =
+            sqlx::query_scalar(r#"SELECT DISTINCT time_zone FROM Banners WHERE user_name = ?"#)
+                .bind(user_name)
+                .fetch_all(&self.database)
+                .await?;
+
+        Ok(AiringOrder::new(request.taken_at, &time_zones))
+    }
+
+    pub async fn next_airings(&self, token: String) -> Result<Vec<NextAiring>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let now = time::OffsetDateTime::now_utc();
+
+        let banners: Vec<Banner> = sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ?"#)
+            .bind(user_name)
+            .fetch_all(&self.database)
+            .await?;
+
+        // a banner whose release day, time or time zone cannot be read has no next airing
+        Ok(banners
+            .iter()
+            .filter_map(|banner| {
+                let next_airing = next_airing(banner, now)
+                    .ok()?
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .unwrap();
+
+                Some(NextAiring {
+                    banner_id: banner.banner_id,
+                    next_airing,
+                })
+            })
+            .collect())
+    }
+
+    pub async fn get_weekly_schedule(
+        &self,
+        week_start: String,
+        time_zone: String,
+        token: String,
+    ) -> Result<WeeklySchedule, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let week_start = parse_date("week_start", &week_start)?;
+        let time_zone = parse_time_zone(&time_zone)?;
+
+        let banners: Vec<Banner> =
+            sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ? AND status = ?"#)
+                .bind(user_name)
+                .bind(WatchStatus::Watching)
+                .fetch_all(&self.database)
+                .await?;
+
+        weekly_schedule(
+            &banners,
+            week_start,
+            time_zone,
+            time::OffsetDateTime::now_utc(),
         )
-        .bind(user_name)
-        .bind(page_size as i64)
-        .bind(page_count as i64 * page_size as i64)
-        .fetch_all(&self.database)
-        .await
-        .map_err(|e| e.to_string())
+    }
+
+    pub async fn export_ics(&self, token: String) -> Result<IcsExport, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+
+        let banners: Vec<Banner> =
+            sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ? ORDER BY banner_id"#)
+                .bind(user_name)
+                .fetch_all(&self.database)
+                .await?;
+
+        Ok(banners_to_ics(&banners, time::OffsetDateTime::now_utc()))
+    }
+
+    pub async fn import_mal(
+        &self,
+        data: Vec<u8>,
+        token: String,
+    ) -> Result<ImportReport, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let entries = parse_mal_export(&data, time::OffsetDateTime::now_utc().date())?;
+
+        self.import_banners(entries, &user_name).await
+    }
+
+    pub async fn export_mal(&self, token: String) -> Result<MalExport, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+
+        let banners: Vec<Banner> =
+            sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ? ORDER BY banner_id"#)
+                .bind(&user_name)
+                .fetch_all(&self.database)
+                .await?;
+
+        Ok(banners_to_mal_xml(&banners, &user_name))
+    }
+
+    pub async fn export_csv(&self, token: String) -> Result<String, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+
+        let banners: Vec<Banner> =
+            sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ? ORDER BY banner_id"#)
+                .bind(&user_name)
+                .fetch_all(&self.database)
+                .await?;
+
+        banners_to_csv(&banners)
+    }
+
+    // rows naming an existing banner by banner_id or title update it, the rest are inserted
+    pub async fn import_csv(
+        &self,
+        data: Vec<u8>,
+        mapping: HashMap<String, String>,
+        dry_run: bool,
+        token: String,
+    ) -> Result<ImportReport, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let rows = parse_csv(&data, &mapping)?;
+
+        let mut report = ImportReport {
+            dry_run,
+            ..Default::default()
+        };
+        let mut transaction = self.database.begin().await?;
+
+        let mut existing = ExistingBanners::default();
+        let mut banners = HashMap::new();
+        for banner in fetch_user_banners(&mut transaction, &user_name).await? {
+            existing.insert(banner.banner_id, &banner.title, banner.mal_id);
+            banners.insert(banner.banner_id, banner);
+        }
+
+        for row in rows {
+            let row = match row {
+                Ok(row) => row,
+                Err(issue) => {
+                    report.invalid.push(issue);
+                    continue;
+                }
+            };
+
+            let target = match row.banner_id() {
+                Ok(Some(banner_id)) => banners.get(&banner_id).cloned().map(Some),
+                Ok(None) => Some(
+                    row.title()
+                        .and_then(|title| existing.find_title(title))
+                        .map(|banner_id| banners[&banner_id].clone()),
+                ),
+                Err(error) => {
+                    report.invalid.push(row.issue(error));
+                    continue;
+                }
+            };
+
+            // a banner_id the user does not own is treated like one that does not exist
+            let Some(target) = target else {
+                report.invalid.push(row.issue(RepoError::NotFound));
+                continue;
+            };
+
+            let mut banner = target.clone().unwrap_or_else(new_banner);
+            if let Err(error) = row
+                .apply(&mut banner)
+                .and_then(|_| prepare_import(&mut banner))
+            {
+                report.invalid.push(row.issue(error));
+                continue;
+            }
+
+            let target_id = target.as_ref().map(|target| target.banner_id);
+            if let Some(banner_id) = existing.conflict(&banner, target_id) {
+                report.conflicts.push(ImportConflict {
+                    row: row.row,
+                    title: banner.title,
+                    banner_id,
+                });
+                continue;
+            }
+
+            match target {
+                Some(target) if target == banner => report.unchanged.push(banner.title),
+                Some(target) => {
+                    update_imported_banner(&mut transaction, &banner).await?;
+                    log_action(
+                        &mut transaction,
+                        &user_name,
+                        "import",
+                        &banner.title,
+                        Some("updated"),
+                    )
+                    .await?;
+
+                    existing.remove(&target.title, target.mal_id);
+                    existing.insert(banner.banner_id, &banner.title, banner.mal_id);
+                    report.updated.push(banner.title.clone());
+                    banners.insert(banner.banner_id, banner);
+                }
+                None => {
+                    let banner_id =
+                        insert_banner(&mut transaction, &banner, None, &user_name).await?;
+                    log_action(&mut transaction, &user_name, "import", &banner.title, None).await?;
+
+                    banner.banner_id = banner_id;
+                    existing.insert(banner_id, &banner.title, banner.mal_id);
+                    report.inserted.push(banner.title.clone());
+                    banners.insert(banner_id, banner);
+                }
+            }
+        }
+
+        if dry_run {
+            transaction.rollback().await?;
+        } else {
+            transaction.commit().await?;
+        }
+
+        Ok(report)
+    }
+
+    // inserts every valid entry that does not match an existing banner, all or nothing
+    async fn import_banners(
+        &self,
+        entries: Vec<Result<Banner, ImportIssue>>,
+        user_name: &str,
+    ) -> Result<ImportReport, RepoError> {
+        let mut report = ImportReport::default();
+        let mut transaction = self.database.begin().await?;
+
+        let mut existing = ExistingBanners::default();
+        for banner in fetch_user_banners(&mut transaction, user_name).await? {
+            existing.insert(banner.banner_id, &banner.title, banner.mal_id);
+        }
+
+        for (index, entry) in entries.into_iter().enumerate() {
+            let mut banner = match entry {
+                Ok(banner) => banner,
+                Err(issue) => {
+                    report.invalid.push(issue);
+                    continue;
+                }
+            };
+
+            if let Err(error) = prepare_import(&mut banner) {
+                report.invalid.push(ImportIssue {
+                    row: index + 1,
+                    title: banner.title,
+                    error,
+                });
+                continue;
+            }
+
+            if let Some(banner_id) = existing.conflict(&banner, None) {
+                report.conflicts.push(ImportConflict {
+                    row: index + 1,
+                    title: banner.title,
+                    banner_id,
+                });
+                continue;
+            }
+
+            let banner_id = insert_banner(&mut transaction, &banner, None, user_name).await?;
+            log_action(&mut transaction, user_name, "import", &banner.title, None).await?;
+            existing.insert(banner_id, &banner.title, banner.mal_id);
+            report.inserted.push(banner.title);
+        }
+
+        transaction.commit().await?;
+
+        Ok(report)
+    }
+
+    pub async fn list_snapshots(
+        &self,
+        config: &SnapshotConfig,
+        token: String,
+    ) -> Result<Vec<SnapshotInfo>, RepoError> {
+        self.resolve_admin(&token).await?;
+        list_snapshots(&self.database, config).await
+    }
+
+    pub async fn take_snapshot(
+        &self,
+        config: &SnapshotConfig,
+        token: String,
+    ) -> Result<SnapshotInfo, RepoError> {
+        self.resolve_admin(&token).await?;
+        take_snapshot(&self.database, config).await
+    }
+
+    // the current state is snapshotted first, so a rollback can itself be rolled back
+    pub async fn restore_snapshot(
+        &self,
+        config: &SnapshotConfig,
+        name: String,
+        token: String,
+    ) -> Result<SnapshotInfo, RepoError> {
+        self.resolve_admin(&token).await?;
+
+        let snapshots = list_snapshots(&self.database, config).await?;
+        let Some(snapshot) = snapshots.iter().find(|snapshot| snapshot.name == name) else {
+            return Err(RepoError::NotFound);
+        };
+        // refused before the current state is snapshotted for nothing
+        if !snapshot.compatible {
+            return Err(RepoError::validation(
+                "snapshot",
+                "was taken with a different database version",
+            ));
+        }
+
+        // rotating before the rollback could delete the snapshot being restored
+        let current = write_snapshot(&self.database, config).await?;
+        restore_snapshot(&self.database, config, &name).await?;
+        rotate_snapshots(config)?;
+
+        Ok(current)
+    }
+
+    pub async fn create_backup(&self, path: PathBuf, token: String) -> Result<(), RepoError> {
+        self.resolve_admin(&token).await?;
+        let mut transaction = self.database.begin().await?;
+
+        let users: Vec<BackupUser> = sqlx::query_as(
+            r#"SELECT user_name, user_type, password FROM Users ORDER BY user_name"#,
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+
+        let banners: Vec<OwnedBanner> =
+            sqlx::query_as(r#"SELECT * FROM Banners ORDER BY banner_id"#)
+                .fetch_all(&mut transaction)
+                .await?;
+
+        let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+        let tag_rows: Vec<(i64, String)> =
+            sqlx::query_as(r#"SELECT banner_id, tag FROM BannerTags ORDER BY tag"#)
+                .fetch_all(&mut transaction)
+                .await?;
+        for (banner_id, tag) in tag_rows {
+            tags.entry(banner_id).or_default().push(tag);
+        }
+
+        let banners = banners
+            .into_iter()
+            .map(|OwnedBanner { banner, user_name }| BackupBanner {
+                tags: tags.remove(&banner.banner_id).unwrap_or_default(),
+                banner,
+                user_name,
+            })
+            .collect();
+
+        let logs: Vec<BackupLog> = sqlx::query_as(
+            r#"
+            SELECT user_name, action, banner_title, details, timestamp
+            FROM Logs
+            ORDER BY log_id
+            "#,
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+
+        let image_hashes: Vec<String> =
+            sqlx::query_scalar(r#"SELECT image_hash FROM Images ORDER BY image_hash"#)
+                .fetch_all(&mut transaction)
+                .await?;
+
+        let manifest = BackupManifest {
+            version: BACKUP_VERSION,
+            created_at: time::OffsetDateTime::now_utc()
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap(),
+            users,
+            banners,
+            logs,
+            images: image_hashes,
+        };
+
+        let file = File::create(&path).map_err(|e| RepoError::database(e.to_string()))?;
+        let written = match write_backup(&mut transaction, BufWriter::new(file), &manifest).await {
+            Ok(()) => transaction.commit().await.map_err(RepoError::from),
+            Err(error) => Err(error),
+        };
+
+        // a half written archive would only fail to restore later
+        if written.is_err() {
+            let _ = fs::remove_file(&path);
+        }
+        written
+    }
+
+    // restores everything in one transaction, so a failed restore changes nothing
+    pub async fn restore_backup(
+        &self,
+        path: PathBuf,
+        policy: MergePolicy,
+        token: String,
+    ) -> Result<RestoreReport, RepoError> {
+        self.resolve_admin(&token).await?;
+        let file = File::open(&path).map_err(|e| RepoError::validation("path", e.to_string()))?;
+        let (mut backup, manifest) = BackupReader::open(BufReader::new(file))?;
+
+        let mut report = RestoreReport::default();
+        let mut transaction = self.database.begin().await?;
+
+        for user in &manifest.users {
+            let exists: bool =
+                sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM Users WHERE user_name = ?)"#)
+                    .bind(&user.user_name)
+                    .fetch_one(&mut transaction)
+                    .await?;
+
+            if !exists {
+                sqlx::query(
+                    r#"INSERT INTO Users (user_type, password, user_name) VALUES (?, ?, ?)"#,
+                )
+                .bind(user.user_type)
+                .bind(&user.password)
+                .bind(&user.user_name)
+                .execute(&mut transaction)
+                .await?;
+                report.users_added += 1;
+            } else if policy == MergePolicy::PreferBackup {
+                sqlx::query(r#"UPDATE Users SET user_type = ?, password = ? WHERE user_name = ?"#)
+                    .bind(user.user_type)
+                    .bind(&user.password)
+                    .bind(&user.user_name)
+                    .execute(&mut transaction)
+                    .await?;
+                report.users_updated += 1;
+            } else {
+                report.users_skipped += 1;
+            }
+        }
+
+        // a bad image fails the transaction, so nothing of the backup is kept
+        for hash in &manifest.images {
+            let image = backup.read_image(hash)?;
+            store_image(&mut transaction, &image.binary, image.thumbnail.as_deref()).await?;
+        }
+
+        for entry in manifest.banners {
+            let mut banner = entry.banner;
+            banner.title = normalize_title(&banner.title)?;
+
+            let existing: Option<i64> = sqlx::query_scalar(
+                r#"SELECT banner_id FROM Banners WHERE user_name = ? AND title = ?"#,
+            )
+            .bind(&entry.user_name)
+            .bind(&banner.title)
+            .fetch_optional(&mut transaction)
+            .await?;
+
+            // a MyAnimeList id already used by another banner is dropped rather than failing
+            let mal_id_taken: bool = sqlx::query_scalar(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM Banners
+                    WHERE user_name = ? AND mal_id = ? AND banner_id IS NOT ?
+                )
+                "#,
+            )
+            .bind(&entry.user_name)
+            .bind(banner.mal_id)
+            .bind(existing)
+            .fetch_one(&mut transaction)
+            .await?;
+            if mal_id_taken {
+                banner.mal_id = None;
+            }
+
+            let banner_id = match existing {
+                None => {
+                    report.banners_added += 1;
+                    insert_banner(
+                        &mut transaction,
+                        &banner,
+                        banner.image_hash.as_deref(),
+                        &entry.user_name,
+                    )
+                    .await?
+                }
+                Some(banner_id) if policy == MergePolicy::PreferBackup => {
+                    banner.banner_id = banner_id;
+                    update_imported_banner(&mut transaction, &banner).await?;
+                    sqlx::query(r#"UPDATE Banners SET image_hash = ? WHERE banner_id = ?"#)
+                        .bind(&banner.image_hash)
+                        .bind(banner_id)
+                        .execute(&mut transaction)
+                        .await?;
+                    report.banners_updated += 1;
+                    banner_id
+                }
+                Some(_) => {
+                    report.banners_skipped += 1;
+                    continue;
+                }
+            };
+
+            replace_tags(&mut transaction, banner_id, &normalize_tags(&entry.tags)?).await?;
+        }
+
+        // restoring the same backup twice must not repeat its history
+        for log in &manifest.logs {
+            let added = sqlx::query(
+                r#"
+                INSERT INTO Logs (user_name, action, banner_title, details, timestamp)
+                SELECT ?, ?, ?, ?, ?
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM Logs
+                    WHERE user_name IS ? AND action = ? AND banner_title IS ?
+                        AND details IS ? AND timestamp = ?
+                )
+                "#,
+            )
+            .bind(&log.user_name)
+            .bind(&log.action)
+            .bind(&log.banner_title)
+            .bind(&log.details)
+            .bind(&log.timestamp)
+            .bind(&log.user_name)
+            .bind(&log.action)
+            .bind(&log.banner_title)
+            .bind(&log.details)
+            .bind(&log.timestamp)
+            .execute(&mut transaction)
+            .await?
+            .rows_affected();
+
+            report.logs_added += added as usize;
+        }
+
+        remove_orphaned_images(&mut transaction).await?;
+        transaction.commit().await?;
+
+        Ok(report)
+    }
+
+    pub async fn query_banners(
+        &self,
+        query: BannerQuery,
+        page_size: usize,
+        cursor: Option<String>,
+        token: String,
+    ) -> Result<Page<Banner>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let request = PageRequest::new(page_size, cursor)?;
+        let query = query.validate()?;
+        let airing_order = self.airing_order(&user_name, &request).await?;
+
+        fetch_page(&self.database, &request, &query.order(), |builder| {
+            query.push_listing(builder, &user_name, &airing_order)
+        })
+        .await
+    }
+
+    pub async fn get_paged_banners(
+        &self,
+        status: Option<WatchStatus>,
+        page_size: usize,
+        cursor: Option<String>,
+        token: String,
+    ) -> Result<Page<Banner>, RepoError> {
+        let user_name = self.resolve_session(&token).await?;
+        let request = PageRequest::new(page_size, cursor)?;
+
+        fetch_page(&self.database, &request, &[], |builder| {
+            builder
+                .push(
+                    r#"
+            SELECT * FROM Banners
+            WHERE user_name = "#,
+                )
+                .push_bind(user_name.as_str())
+                .push(" AND (")
+                .push_bind(status)
+                .push(" IS NULL OR status = ")
+                .push_bind(status)
+                .push(")");
+        })
+        .await
+    }
+}
+
+async fn insert_banner(
+    transaction: &mut Transaction<'_, Sqlite>,
+    banner: &Banner,
+    image_hash: Option<&str>,
+    user_name: &str,
+) -> Result<i64, RepoError> {
+    let banner_id = sqlx::query(
+        r#"
+        INSERT INTO Banners (
+            image_hash,
+            title,
+            alternate_titles,
+            notes,
+            release_day,
+            release_time,
+            time_zone,
+            current_episodes,
+            total_episodes,
+            status,
+            mal_id,
+            user_name
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(image_hash)
+    .bind(&banner.title)
+    .bind(&banner.alternate_titles)
+    .bind(&banner.notes)
+    .bind(&banner.release_day)
+    .bind(&banner.release_time)
+    .bind(&banner.time_zone)
+    .bind(banner.current_episodes as i64)
+    .bind(banner.total_episodes as i64)
+    .bind(
+        banner
+            .status
+            .for_episodes(banner.current_episodes, banner.total_episodes),
+    )
+    .bind(banner.mal_id)
+    .bind(user_name)
+    .execute(&mut *transaction)
+    .await?
+    .last_insert_rowid();
+
+    Ok(banner_id)
+}
+
+// the checks and image processing add_banner does before opening a transaction
+async fn prepare_new_banner(banner: &mut Banner) -> Result<Option<ProcessedImage>, RepoError> {
+    banner.title = normalize_title(&banner.title)?;
+    validate_banner(banner)?;
+    process_upload(std::mem::take(&mut banner.image_binary)).await
+}
+
+// Some(None) removes the cover, None leaves it as it is
+async fn prepare_patch_image(
+    patch: &mut BannerPatch,
+) -> Result<Option<Option<ProcessedImage>>, RepoError> {
+    match patch.image_binary.take() {
+        Some(image_binary) => {
+            validate_image(&image_binary)?;
+            Ok(Some(process_upload(image_binary).await?))
+        }
+        None => Ok(None),
+    }
+}
+
+async fn add_user_banner(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    banner: &Banner,
+    image: Option<ProcessedImage>,
+) -> Result<i64, RepoError> {
+    let image_hash = match &image {
+        Some(image) => Some(store_image(transaction, &image.binary, Some(&image.thumbnail)).await?),
+        None => None,
+    };
+
+    let banner_id = insert_banner(transaction, banner, image_hash.as_deref(), user_name).await?;
+
+    log_action(transaction, user_name, "add", &banner.title, None).await?;
+
+    Ok(banner_id)
+}
+
+async fn delete_user_banner(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    banner_id: i64,
+) -> Result<(), RepoError> {
+    let title: String = sqlx::query_scalar(
+        r#"DELETE FROM Banners WHERE banner_id = ? AND user_name = ? RETURNING title"#,
+    )
+    .bind(banner_id)
+    .bind(user_name)
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or(RepoError::NotFound)?;
+
+    remove_orphaned_images(transaction).await?;
+    log_action(transaction, user_name, "delete", &title, None).await?;
+
+    Ok(())
+}
+
+async fn rename_user_banner(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    banner_id: i64,
+    title: &str,
+) -> Result<(), RepoError> {
+    let title: String = sqlx::query_scalar(
+        r#"
+        UPDATE Banners
+        SET title = ?
+        WHERE banner_id = ? AND user_name = ?
+        RETURNING title"#,
+    )
+    .bind(title)
+    .bind(banner_id)
+    .bind(user_name)
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or(RepoError::NotFound)?;
+
+    log_action(transaction, user_name, "rename", &title, None).await?;
+
+    Ok(())
+}
+
+async fn set_user_banner_status(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    banner_id: i64,
+    status: WatchStatus,
+) -> Result<(), RepoError> {
+    let (title, previous_status): (String, WatchStatus) = sqlx::query_as(
+        r#"SELECT title, status FROM Banners WHERE banner_id = ? AND user_name = ?"#,
+    )
+    .bind(banner_id)
+    .bind(user_name)
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or(RepoError::NotFound)?;
+
+    if status == previous_status {
+        return Ok(());
+    }
+
+    sqlx::query(r#"UPDATE Banners SET status = ? WHERE banner_id = ? AND user_name = ?"#)
+        .bind(status)
+        .bind(banner_id)
+        .bind(user_name)
+        .execute(&mut *transaction)
+        .await?;
+
+    log_action(
+        transaction,
+        user_name,
+        "update status",
+        &title,
+        Some(&format!("status: {:?} -> {:?}", previous_status, status)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn update_user_banner(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    banner_id: i64,
+    patch: &BannerPatch,
+    image: Option<Option<ProcessedImage>>,
+) -> Result<(), RepoError> {
+    let image_hash = image
+        .as_ref()
+        .map(|image| image.as_ref().map(|image| image_hash(&image.binary)));
+
+    let banner: Banner =
+        sqlx::query_as(r#"SELECT * FROM Banners WHERE banner_id = ? AND user_name = ?"#)
+            .bind(banner_id)
+            .bind(user_name)
+            .fetch_optional(&mut *transaction)
+            .await?
+            .ok_or(RepoError::NotFound)?;
+
+    validate_patch(patch, &banner)?;
+
+    let mut changes = patch.describe_changes(&banner);
+
+    if let Some(image_hash) = &image_hash {
+        if *image_hash != banner.image_hash {
+            changes.push(format!(
+                "image: {} -> {}",
+                banner.image_hash.as_deref().unwrap_or("none"),
+                image_hash.as_deref().unwrap_or("none")
+            ));
+        }
+    }
+
+    let tags = patch.tags.as_deref().map(normalize_tags).transpose()?;
+    if let Some(tags) = &tags {
+        let previous_tags = fetch_tags(transaction, banner_id).await?;
+        if *tags != previous_tags {
+            changes.push(format!("tags: {:?} -> {:?}", previous_tags, tags));
+        }
+    }
+
+    let current_episodes = patch.current_episodes.unwrap_or(banner.current_episodes);
+    let total_episodes = patch.total_episodes.unwrap_or(banner.total_episodes);
+    let status = match (patch.current_episodes, patch.total_episodes) {
+        (None, None) => banner.status,
+        _ => banner.status.for_episodes(current_episodes, total_episodes),
+    };
+
+    if status != banner.status {
+        changes.push(format!("status: {:?} -> {:?}", banner.status, status));
+    }
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(Some(image)) = &image {
+        store_image(transaction, &image.binary, Some(&image.thumbnail)).await?;
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE Banners
+        SET image_hash = CASE WHEN ? THEN ? ELSE image_hash END,
+            alternate_titles = COALESCE(?, alternate_titles),
+            notes = COALESCE(?, notes),
+            release_day = COALESCE(?, release_day),
+            release_time = COALESCE(?, release_time),
+            time_zone = COALESCE(?, time_zone),
+            current_episodes = COALESCE(?, current_episodes),
+            total_episodes = COALESCE(?, total_episodes),
+            status = ?
+        WHERE banner_id = ? AND user_name = ?"#,
+    )
+    .bind(image_hash.is_some())
+    .bind(image_hash.flatten())
+    .bind(&patch.alternate_titles)
+    .bind(&patch.notes)
+    .bind(&patch.release_day)
+    .bind(&patch.release_time)
+    .bind(&patch.time_zone)
+    .bind(patch.current_episodes)
+    .bind(patch.total_episodes)
+    .bind(status)
+    .bind(banner_id)
+    .bind(user_name)
+    .execute(&mut *transaction)
+    .await?;
+
+    if let Some(tags) = &tags {
+        replace_tags(transaction, banner_id, tags).await?;
+    }
+
+    remove_orphaned_images(transaction).await?;
+    log_action(
+        transaction,
+        user_name,
+        "update",
+        &banner.title,
+        Some(&changes.join("; ")),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// what earlier edits in a sync batch did, so later ones can build on them
+#[derive(Default)]
+struct SyncBatch {
+    // banners added in this batch, by the negative id the client gave them
+    added: HashMap<i64, i64>,
+    // the base version the client last sent for a banner and the version its edit produced
+    versions: HashMap<i64, (i64, i64)>,
+}
+
+impl SyncBatch {
+    fn resolve(&self, banner_id: i64) -> Result<i64, RepoError> {
+        match banner_id {
+            banner_id if banner_id < 0 => self
+                .added
+                .get(&banner_id)
+                .copied()
+                .ok_or(RepoError::NotFound),
+            banner_id => Ok(banner_id),
+        }
+    }
+
+    // edits queued one after another offline all carry the version the client started from
+    fn is_stale(&self, banner_id: i64, base_version: i64, current_version: i64) -> bool {
+        base_version != current_version
+            && self.versions.get(&banner_id) != Some(&(base_version, current_version))
+    }
+
+    fn record(&mut self, change: &SyncChange, outcome: &SyncOutcome) {
+        let SyncOutcome::Applied { banner_id, version } = outcome else {
+            return;
+        };
+
+        match (&change.operation, change.base_version, version) {
+            (SyncOperation::AddBanner { banner }, _, _) if banner.banner_id < 0 => {
+                self.added.insert(banner.banner_id, *banner_id);
+            }
+            (_, Some(base_version), Some(version)) => {
+                self.versions.insert(*banner_id, (base_version, *version));
+            }
+            _ => {}
+        }
+    }
+}
+
+// the checks an edit can fail before the transaction is opened
+async fn prepare_sync_operation(
+    operation: &mut SyncOperation,
+) -> Result<Option<Option<ProcessedImage>>, RepoError> {
+    match operation {
+        SyncOperation::AddBanner { banner } => Ok(Some(prepare_new_banner(banner).await?)),
+        SyncOperation::RenameBanner { title, .. } => {
+            *title = normalize_title(title)?;
+            Ok(None)
+        }
+        SyncOperation::UpdateBanner { patch, .. } => prepare_patch_image(patch).await,
+        SyncOperation::DeleteBanner { .. } | SyncOperation::SetBannerStatus { .. } => Ok(None),
+    }
+}
+
+async fn apply_sync_change(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    batch: &SyncBatch,
+    change: &SyncChange,
+    image: Option<Option<ProcessedImage>>,
+) -> Result<SyncOutcome, RepoError> {
+    let base_version = change.base_version;
+
+    let banner_id = match &change.operation {
+        SyncOperation::AddBanner { banner } => {
+            add_user_banner(transaction, user_name, banner, image.flatten()).await?
+        }
+        SyncOperation::DeleteBanner { banner_id } => {
+            let banner_id = batch.resolve(*banner_id)?;
+            match check_sync_base(transaction, user_name, batch, banner_id, base_version).await {
+                Ok(None) => delete_user_banner(transaction, user_name, banner_id).await?,
+                Ok(Some(conflict)) => return Ok(conflict),
+                // deleting a banner that is already gone leaves the same result
+                Err(RepoError::NotFound) => {}
+                Err(error) => return Err(error),
+            }
+            banner_id
+        }
+        SyncOperation::RenameBanner { banner_id, title } => {
+            let banner_id = batch.resolve(*banner_id)?;
+            if let Some(conflict) =
+                check_sync_base(transaction, user_name, batch, banner_id, base_version).await?
+            {
+                return Ok(conflict);
+            }
+            rename_user_banner(transaction, user_name, banner_id, title).await?;
+            banner_id
+        }
+        SyncOperation::SetBannerStatus { banner_id, status } => {
+            let banner_id = batch.resolve(*banner_id)?;
+            if let Some(conflict) =
+                check_sync_base(transaction, user_name, batch, banner_id, base_version).await?
+            {
+                return Ok(conflict);
+            }
+            set_user_banner_status(transaction, user_name, banner_id, *status).await?;
+            banner_id
+        }
+        SyncOperation::UpdateBanner { banner_id, patch } => {
+            let banner_id = batch.resolve(*banner_id)?;
+            if let Some(conflict) =
+                check_sync_base(transaction, user_name, batch, banner_id, base_version).await?
+            {
+                return Ok(conflict);
+            }
+            update_user_banner(transaction, user_name, banner_id, patch, image).await?;
+            banner_id
+        }
+    };
+
+    Ok(SyncOutcome::Applied {
+        banner_id,
+        version: fetch_version(transaction, user_name, banner_id).await?,
+    })
+}
+
+// the conflict an edit to an existing banner runs into, if the banner moved past its base version
+async fn check_sync_base(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    batch: &SyncBatch,
+    banner_id: i64,
+    base_version: Option<i64>,
+) -> Result<Option<SyncOutcome>, RepoError> {
+    let current_version = fetch_version(transaction, user_name, banner_id)
+        .await?
+        .ok_or(RepoError::NotFound)?;
+
+    match base_version {
+        Some(base_version) if batch.is_stale(banner_id, base_version, current_version) => {
+            let banner =
+                sqlx::query_as(r#"SELECT * FROM Banners WHERE banner_id = ? AND user_name = ?"#)
+                    .bind(banner_id)
+                    .bind(user_name)
+                    .fetch_one(&mut *transaction)
+                    .await?;
+            Ok(Some(SyncOutcome::Conflict { banner }))
+        }
+        _ => Ok(None),
+    }
+}
+
+async fn fetch_version(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    banner_id: i64,
+) -> Result<Option<i64>, RepoError> {
+    sqlx::query_scalar(r#"SELECT version FROM Banners WHERE banner_id = ? AND user_name = ?"#)
+        .bind(banner_id)
+        .bind(user_name)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(RepoError::from)
+}
+
+// images are read one at a time, so a large library never sits in memory at once
+async fn write_backup<W: Write + Seek>(
+    transaction: &mut Transaction<'_, Sqlite>,
+    writer: W,
+    manifest: &BackupManifest,
+) -> Result<(), RepoError> {
+    let mut backup = BackupWriter::new(writer, manifest)?;
+
+    for hash in &manifest.images {
+        let (binary, thumbnail): (Vec<u8>, Option<Vec<u8>>) = sqlx::query_as(
+            r#"SELECT image_binary, thumbnail_binary FROM Images WHERE image_hash = ?"#,
+        )
+        .bind(hash)
+        .fetch_one(&mut *transaction)
+        .await?;
+        backup.add_image(hash, &BackupImage { binary, thumbnail })?;
+    }
+
+    backup
+        .finish()?
+        .flush()
+        .map_err(|e| RepoError::database(e.to_string()))
+}
+
+// matches add_banner's checks, so imported banners are held to the same rules
+fn prepare_import(banner: &mut Banner) -> Result<(), RepoError> {
+    banner.title = normalize_title(&banner.title)?;
+    validate_banner(banner)?;
+
+    // applied here as well as on write, so a dry run reports the status that would be stored
+    banner.status = banner
+        .status
+        .for_episodes(banner.current_episodes, banner.total_episodes);
+
+    Ok(())
+}
+
+async fn fetch_user_banners(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+) -> Result<Vec<Banner>, RepoError> {
+    sqlx::query_as(r#"SELECT * FROM Banners WHERE user_name = ? ORDER BY banner_id"#)
+        .bind(user_name)
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(RepoError::from)
+}
+
+async fn update_imported_banner(
+    transaction: &mut Transaction<'_, Sqlite>,
+    banner: &Banner,
+) -> Result<(), RepoError> {
+    sqlx::query(
+        r#"
+        UPDATE Banners SET
+            title = ?,
+            alternate_titles = ?,
+            notes = ?,
+            release_day = ?,
+            release_time = ?,
+            time_zone = ?,
+            current_episodes = ?,
+            total_episodes = ?,
+            status = ?,
+            mal_id = ?
+        WHERE banner_id = ?
+        "#,
+    )
+    .bind(&banner.title)
+    .bind(&banner.alternate_titles)
+    .bind(&banner.notes)
+    .bind(&banner.release_day)
+    .bind(&banner.release_time)
+    .bind(&banner.time_zone)
+    .bind(banner.current_episodes as i64)
+    .bind(banner.total_episodes as i64)
+    .bind(
+        banner
+            .status
+            .for_episodes(banner.current_episodes, banner.total_episodes),
+    )
+    .bind(banner.mal_id)
+    .bind(banner.banner_id)
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+async fn fetch_tags(
+    transaction: &mut Transaction<'_, Sqlite>,
+    banner_id: i64,
+) -> Result<Vec<String>, RepoError> {
+    sqlx::query_scalar(r#"SELECT tag FROM BannerTags WHERE banner_id = ? ORDER BY tag"#)
+        .bind(banner_id)
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(RepoError::from)
+}
+
+async fn replace_tags(
+    transaction: &mut Transaction<'_, Sqlite>,
+    banner_id: i64,
+    tags: &[String],
+) -> Result<(), RepoError> {
+    sqlx::query(r#"DELETE FROM BannerTags WHERE banner_id = ?"#)
+        .bind(banner_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    for tag in tags {
+        sqlx::query(r#"INSERT INTO BannerTags (banner_id, tag) VALUES (?, ?)"#)
+            .bind(banner_id)
+            .bind(tag)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn log_action(
+    transaction: &mut Transaction<'_, Sqlite>,
+    user_name: &str,
+    action: &str,
+    banner_title: &str,
+    details: Option<&str>,
+) -> Result<(), RepoError> {
+    let timestamp = time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .unwrap();
+
+    sqlx::query(
+        r#"
+        INSERT INTO Logs (user_name, action, banner_title, details, timestamp)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(user_name)
+    .bind(action)
+    .bind(banner_title)
+    .bind(details)
+    .bind(timestamp)
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::banner_query::{BannerFilter, SortField, SortKey};
+    use crate::set_up_database;
+    use crate::validation::Weekday;
+    use serde_json::json;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    // set_up_database creates admin/adminpassword and bobross/pass
+    async fn test_repo() -> (TempDir, BannerRepo) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = set_up_database(&dir.path().join("sqlite.db"))
+            .await
+            .unwrap();
+
+        (dir, BannerRepo::new(db))
+    }
+
+    async fn login(repo: &BannerRepo, user_name: &str, password: &str) -> String {
+        match repo.login(user_name.into(), password.into()).await.unwrap() {
+            LoginResult::Admin { token } | LoginResult::User { token } => token,
+            LoginResult::Fail { error } => panic!("{user_name} could not log in: {error}"),
+        }
+    }
+
+    fn banner(title: &str, release_day: &str) -> Banner {
+        serde_json::from_value(json!({
+            "title": title,
+            "release_day": release_day,
+            "release_time": "12:00",
+            "current_episodes": 1,
+            "total_episodes": 12,
+        }))
+        .unwrap()
+    }
+
+    fn cover() -> Vec<u8> {
+        let mut png = Cursor::new(vec![]);
+        image::DynamicImage::new_rgb8(40, 60)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    async fn titles(repo: &BannerRepo, token: &str) -> Vec<String> {
+        let mut titles: Vec<String> = repo
+            .get_all_banners(token.into())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|banner| banner.title)
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    async fn find(repo: &BannerRepo, token: &str, title: &str) -> Banner {
+        repo.get_all_banners(token.into())
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|banner| banner.title == title)
+            .unwrap()
+    }
+
+    fn page_titles(page: &Page<Banner>) -> Vec<&str> {
+        page.items
+            .iter()
+            .map(|banner| banner.title.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn expired_and_revoked_sessions_are_refused_and_cleared() {
+        let (_dir, repo) = test_repo().await;
+        let revoked = login(&repo, "bobross", "pass").await;
+        let expired = login(&repo, "bobross", "pass").await;
+
+        repo.logout(revoked.clone()).await.unwrap();
+        sqlx::query("UPDATE Sessions SET expires_at = '2000-01-01T00:00:00Z' WHERE token = ?")
+            .bind(&expired)
+            .execute(&repo.database)
+            .await
+            .unwrap();
+
+        for token in [&revoked, &expired] {
+            assert!(matches!(
+                repo.resolve_session(token).await,
+                Err(RepoError::Unauthorized)
+            ));
+        }
+
+        let current = login(&repo, "bobross", "pass").await;
+        assert_eq!(repo.resolve_session(&current).await.unwrap(), "bobross");
+
+        let tokens: Vec<String> = sqlx::query_scalar("SELECT token FROM Sessions")
+            .fetch_all(&repo.database)
+            .await
+            .unwrap();
+        assert_eq!(tokens, [current]);
+    }
+
+    #[tokio::test]
+    async fn titles_differing_only_in_case_are_different_banners() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        repo.add_banner(banner("Naruto", "Monday"), token.clone())
+            .await
+            .unwrap();
+        let bleach = repo
+            .add_banner(banner("Bleach", "Tuesday"), token.clone())
+            .await
+            .unwrap();
+
+        let taken = repo
+            .rename_banner(bleach, "Naruto".into(), token.clone())
+            .await;
+        assert!(matches!(taken, Err(RepoError::Duplicate)));
+        repo.rename_banner(bleach, "naruto".into(), token.clone())
+            .await
+            .unwrap();
+
+        // an import without banner ids matches rows to banners by the same rule
+        let csv = "title,release_day,release_time\nNARUTO,Monday,12:00\nnaruto,Tuesday,12:00\n";
+        let report = repo
+            .import_csv(csv.into(), HashMap::new(), false, token.clone())
+            .await
+            .unwrap();
+        assert_eq!(report.inserted, ["NARUTO"]);
+        assert_eq!(report.unchanged, ["naruto"]);
+        assert_eq!(titles(&repo, &token).await, ["NARUTO", "Naruto", "naruto"]);
+    }
+
+    #[tokio::test]
+    async fn title_pages_continue_after_inserts_before_the_cursor() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        for title in ["Naruto", "Bleach", "Mushishi", "Frieren"] {
+            repo.add_banner(banner(title, "Monday"), token.clone())
+                .await
+                .unwrap();
+        }
+
+        let query = || BannerQuery {
+            filter: BannerFilter::default(),
+            sort: vec![SortKey {
+                field: SortField::Title,
+                direction: SortDirection::Ascending,
+            }],
+        };
+
+        let first = repo
+            .query_banners(query(), 2, None, token.clone())
+            .await
+            .unwrap();
+        assert_eq!(page_titles(&first), ["Bleach", "Frieren"]);
+
+        // an offset would now repeat Frieren
+        repo.add_banner(banner("Cowboy Bebop", "Monday"), token.clone())
+            .await
+            .unwrap();
+        repo.add_banner(banner("Zetman", "Monday"), token.clone())
+            .await
+            .unwrap();
+
+        let second = repo
+            .query_banners(query(), 2, first.next_cursor.clone(), token.clone())
+            .await
+            .unwrap();
+        assert_eq!(page_titles(&second), ["Mushishi", "Naruto"]);
+
+        let third = repo
+            .query_banners(query(), 2, second.next_cursor.clone(), token.clone())
+            .await
+            .unwrap();
+        assert_eq!(page_titles(&third), ["Zetman"]);
+        assert!(third.next_cursor.is_none());
+
+        let back = repo
+            .query_banners(query(), 2, second.prev_cursor.clone(), token.clone())
+            .await
+            .unwrap();
+        assert_eq!(page_titles(&back), ["Cowboy Bebop", "Frieren"]);
+    }
+
+    #[tokio::test]
+    async fn release_day_pages_list_every_banner_once_across_inserts() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        let days = Weekday::ALL.map(|day| format!("{day:?}"));
+        let mut listed_before = vec![];
+        for day in &days {
+            listed_before.push(
+                repo.add_banner(banner(day, day), token.clone())
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let mut seen = vec![];
+        let mut cursor = None;
+        loop {
+            let page = repo
+                .sort_banners_by_release_day(3, cursor, token.clone())
+                .await
+                .unwrap();
+            seen.extend(page.items.iter().map(|banner| banner.banner_id));
+
+            // each page inserts one more banner for every day of the week
+            for day in &days {
+                repo.add_banner(banner(&format!("{day} {}", seen.len()), day), token.clone())
+                    .await
+                    .unwrap();
+            }
+
+            cursor = page.next_cursor;
+            if cursor.is_none() || seen.len() > 100 {
+                break;
+            }
+        }
+
+        let mut unique = seen.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), seen.len());
+        assert!(listed_before
+            .iter()
+            .all(|banner_id| seen.contains(banner_id)));
+    }
+
+    #[tokio::test]
+    async fn ranked_search_pages_cover_every_match_once() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        for title in [
+            "Gundam",
+            "Gundam Wing",
+            "Gundam Unicorn",
+            "Mobile Suit Gundam 00",
+        ] {
+            repo.add_banner(banner(title, "Monday"), token.clone())
+                .await
+                .unwrap();
+        }
+        let mut notes_only = banner("Code Geass", "Sunday");
+        notes_only.notes = String::from("mechs, like gundam");
+        repo.add_banner(notes_only, token.clone()).await.unwrap();
+        repo.add_banner(banner("Mushishi", "Sunday"), token.clone())
+            .await
+            .unwrap();
+
+        let search = |cursor: Option<String>| {
+            repo.search_banners(
+                "gundam".into(),
+                SearchMode::FullText,
+                None,
+                2,
+                cursor,
+                token.clone(),
+            )
+        };
+
+        let first = search(None).await.unwrap();
+        assert_eq!(first.total_count, 5);
+
+        let mut seen = vec![];
+        let mut page = first;
+        loop {
+            seen.extend(page.items.iter().map(|result| result.banner.title.clone()));
+            match page.next_cursor {
+                Some(next) => page = search(Some(next)).await.unwrap(),
+                None => break,
+            }
+        }
+
+        assert_eq!(seen.len(), 5);
+        // titles outweigh notes
+        assert_eq!(seen.last().unwrap(), "Code Geass");
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5);
+
+        let first = search(None).await.unwrap();
+        let second = search(first.next_cursor.clone()).await.unwrap();
+        let back = search(second.prev_cursor.clone()).await.unwrap();
+        let titles = |page: &Page<SearchResult>| {
+            page.items
+                .iter()
+                .map(|result| result.banner.title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&back), titles(&first));
+    }
+
+    #[tokio::test]
+    async fn banners_with_an_unreadable_release_day_are_paged_and_skipped() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        for (title, release_day) in [
+            ("Naruto", "Monday"),
+            ("Bleach", "Tuesday"),
+            ("Mushishi", "Sunday"),
+            ("Frieren", "Friday"),
+        ] {
+            repo.add_banner(banner(title, release_day), token.clone())
+                .await
+                .unwrap();
+        }
+        // as a banner saved before release days were validated may be
+        sqlx::query(
+            "UPDATE Banners SET release_day = 'Someday' WHERE title IN ('Bleach', 'Frieren')",
+        )
+        .execute(&repo.database)
+        .await
+        .unwrap();
+
+        let mut seen = vec![];
+        let mut cursor = None;
+        loop {
+            let page = repo
+                .sort_banners_by_release_day(1, cursor, token.clone())
+                .await
+                .unwrap();
+            seen.extend(page_titles(&page).iter().map(|title| title.to_string()));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, ["Bleach", "Frieren", "Mushishi", "Naruto"]);
+
+        let airings = repo.next_airings(token.clone()).await.unwrap();
+        assert_eq!(airings.len(), 2);
+
+        let schedule = repo
+            .get_weekly_schedule("2026-10-18".into(), "UTC".into(), token.clone())
+            .await
+            .unwrap();
+        let mut skipped = schedule.skipped;
+        skipped.sort();
+        assert_eq!(skipped, ["Bleach", "Frieren"]);
+
+        let calendar = repo.export_ics(token.clone()).await.unwrap();
+        assert_eq!(calendar.skipped, ["Bleach", "Frieren"]);
+        assert_eq!(calendar.ics.matches("BEGIN:VEVENT").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn banners_without_a_time_zone_take_the_users_zone() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        repo.add_banner(banner("Mushishi", "Monday"), token.clone())
+            .await
+            .unwrap();
+        repo.add_banner(banner("Frieren", "Friday"), token.clone())
+            .await
+            .unwrap();
+        // as left by the migration for a banner saved before time zones were stored
+        sqlx::query("UPDATE Banners SET time_zone = '' WHERE title = 'Mushishi'")
+            .execute(&repo.database)
+            .await
+            .unwrap();
+
+        let invalid = repo
+            .fill_missing_time_zones("Not/AZone".into(), token.clone())
+            .await;
+        assert!(matches!(invalid, Err(RepoError::Validation { .. })));
+
+        let filled = repo
+            .fill_missing_time_zones("Europe/Berlin".into(), token.clone())
+            .await
+            .unwrap();
+        assert_eq!(filled, 1);
+        assert_eq!(
+            find(&repo, &token, "Mushishi").await.time_zone,
+            "Europe/Berlin"
+        );
+        assert_eq!(find(&repo, &token, "Frieren").await.time_zone, "Asia/Tokyo");
+    }
+
+    #[tokio::test]
+    async fn mal_export_round_trips_and_reports_banners_without_an_id() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        let mut frieren = banner("Frieren", "Friday");
+        frieren.mal_id = Some(52991);
+        frieren.current_episodes = 3;
+        frieren.total_episodes = 28;
+        frieren.status = WatchStatus::OnHold;
+        repo.add_banner(frieren, token.clone()).await.unwrap();
+        repo.add_banner(banner("Homemade", "Monday"), token.clone())
+            .await
+            .unwrap();
+
+        let export = repo.export_mal(token.clone()).await.unwrap();
+        assert_eq!(export.skipped, ["Homemade"]);
+
+        repo.register_user("alice".into(), "alicepassword".into(), false, None)
+            .await
+            .unwrap();
+        let alice = login(&repo, "alice", "alicepassword").await;
+
+        let report = repo
+            .import_mal(export.xml.clone().into_bytes(), alice.clone())
+            .await
+            .unwrap();
+        assert_eq!(report.inserted, ["Frieren"]);
+        assert!(report.invalid.is_empty());
+
+        let imported = find(&repo, &alice, "Frieren").await;
+        assert_eq!(imported.mal_id, Some(52991));
+        assert_eq!(
+            (imported.current_episodes, imported.total_episodes),
+            (3, 28)
+        );
+        assert_eq!(imported.status, WatchStatus::OnHold);
+
+        // importing the same file again matches the banner instead of adding it twice
+        let again = repo
+            .import_mal(export.xml.into_bytes(), alice.clone())
+            .await
+            .unwrap();
+        assert!(again.inserted.is_empty());
+        assert_eq!(again.conflicts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn csv_export_round_trips_formula_like_cells() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        let mut formula = banner("=HYPERLINK(\"x\")", "Monday");
+        formula.notes = "@everyone".into();
+        repo.add_banner(formula, token.clone()).await.unwrap();
+        let mut quoted = banner("'=already quoted", "Tuesday");
+        quoted.alternate_titles = "-minus".into();
+        repo.add_banner(quoted, token.clone()).await.unwrap();
+        repo.add_banner(banner("'plain", "Friday"), token.clone())
+            .await
+            .unwrap();
+
+        let csv = repo.export_csv(token.clone()).await.unwrap();
+        assert!(csv.contains(r#""'=HYPERLINK(""x"")""#));
+        assert!(csv.contains(",'@everyone,"));
+        assert!(csv.contains(",''=already quoted,'-minus,"));
+        assert!(csv.contains(",'plain,"));
+
+        let report = repo
+            .import_csv(csv.into_bytes(), HashMap::new(), false, token.clone())
+            .await
+            .unwrap();
+        assert!(report.updated.is_empty() && report.inserted.is_empty());
+        assert_eq!(report.unchanged.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn only_an_admin_can_register_another_admin() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+        let admin = login(&repo, "admin", "adminpassword").await;
+
+        for token in [None, Some("forged".into()), Some(token)] {
+            assert!(matches!(
+                repo.register_user("mallory".into(), "pw".into(), true, token)
+                    .await,
+                Err(RepoError::Unauthorized)
+            ));
+        }
+
+        repo.register_user("carol".into(), "pw".into(), true, Some(admin))
+            .await
+            .unwrap();
+        assert!(matches!(
+            repo.login("carol".into(), "pw".into()).await.unwrap(),
+            LoginResult::Admin { .. }
+        ));
+        assert!(matches!(
+            repo.login("mallory".into(), "pw".into()).await.unwrap(),
+            LoginResult::Fail { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn restoring_a_backup_keeps_or_replaces_banners_by_policy() {
+        let (dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+        let admin = login(&repo, "admin", "adminpassword").await;
+        let path = dir.path().join("backup.zip");
+
+        let mut frieren = banner("Frieren", "Friday");
+        frieren.image_binary = cover();
+        frieren.current_episodes = 3;
+        let frieren = repo.add_banner(frieren, token.clone()).await.unwrap();
+        let tags = BannerPatch {
+            tags: Some(vec!["fantasy".into()]),
+            ..Default::default()
+        };
+        repo.update_banner(frieren, tags, token.clone())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            repo.create_backup(path.clone(), token.clone()).await,
+            Err(RepoError::Unauthorized)
+        ));
+        repo.create_backup(path.clone(), admin.clone())
+            .await
+            .unwrap();
+
+        let watched_more = BannerPatch {
+            current_episodes: Some(7),
+            ..Default::default()
+        };
+        repo.update_banner(frieren, watched_more, token.clone())
+            .await
+            .unwrap();
+        repo.add_banner(banner("Mushishi", "Sunday"), token.clone())
+            .await
+            .unwrap();
+
+        let kept = repo
+            .restore_backup(path.clone(), MergePolicy::KeepExisting, admin.clone())
+            .await
+            .unwrap();
+        assert_eq!((kept.banners_added, kept.banners_skipped), (0, 1));
+        assert_eq!(kept.users_skipped, 2);
+        assert_eq!(find(&repo, &token, "Frieren").await.current_episodes, 7);
+
+        let replaced = repo
+            .restore_backup(path.clone(), MergePolicy::PreferBackup, admin.clone())
+            .await
+            .unwrap();
+        assert_eq!(replaced.banners_updated, 1);
+        assert_eq!(find(&repo, &token, "Frieren").await.current_episodes, 3);
+        // a restore merges, banners added since the backup stay
+        assert_eq!(titles(&repo, &token).await, ["Frieren", "Mushishi"]);
+        assert_eq!(
+            repo.get_banner_tags(frieren, token.clone()).await.unwrap(),
+            ["fantasy"]
+        );
+    }
+
+    #[tokio::test]
+    async fn restoring_a_backup_into_a_fresh_database_brings_back_covers() {
+        let (dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+        let admin = login(&repo, "admin", "adminpassword").await;
+        let path = dir.path().join("backup.zip");
+
+        let mut frieren = banner("Frieren", "Friday");
+        frieren.image_binary = cover();
+        repo.add_banner(frieren, token.clone()).await.unwrap();
+        let image_hash = find(&repo, &token, "Frieren").await.image_hash.unwrap();
+        repo.create_backup(path.clone(), admin).await.unwrap();
+
+        let (_fresh_dir, fresh) = test_repo().await;
+        let fresh_admin = login(&fresh, "admin", "adminpassword").await;
+
+        let garbage = dir.path().join("garbage.zip");
+        fs::write(&garbage, b"not a zip").unwrap();
+        assert!(matches!(
+            fresh
+                .restore_backup(garbage, MergePolicy::KeepExisting, fresh_admin.clone())
+                .await,
+            Err(RepoError::Validation { .. })
+        ));
+
+        let report = fresh
+            .restore_backup(path, MergePolicy::KeepExisting, fresh_admin)
+            .await
+            .unwrap();
+        assert_eq!(report.banners_added, 1);
+
+        let fresh_token = login(&fresh, "bobross", "pass").await;
+        let restored = find(&fresh, &fresh_token, "Frieren").await;
+        assert_eq!(restored.image_hash.as_deref(), Some(image_hash.as_str()));
+        assert_eq!(
+            fresh.get_image(&image_hash).await.unwrap(),
+            repo.get_image(&image_hash).await.unwrap()
+        );
+        assert!(fresh.get_thumbnail(&image_hash).await.is_ok());
+    }
+
+    fn snapshot_config(dir: &TempDir) -> SnapshotConfig {
+        SnapshotConfig {
+            dir: dir.path().join("snapshots"),
+            interval: None,
+            keep: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn rolling_back_to_a_snapshot_can_itself_be_rolled_back() {
+        let (dir, repo) = test_repo().await;
+        let config = snapshot_config(&dir);
+        let token = login(&repo, "bobross", "pass").await;
+        let admin = login(&repo, "admin", "adminpassword").await;
+
+        let frieren = repo
+            .add_banner(banner("Frieren", "Friday"), token.clone())
+            .await
+            .unwrap();
+        let mushishi = repo
+            .add_banner(banner("Mushishi", "Sunday"), token.clone())
+            .await
+            .unwrap();
+        let before = repo.take_snapshot(&config, admin.clone()).await.unwrap();
+        assert!(before.compatible);
+
+        repo.delete_banner(frieren, token.clone()).await.unwrap();
+        repo.rename_banner(mushishi, "Mushi-shi".into(), token.clone())
+            .await
+            .unwrap();
+
+        let after = repo
+            .restore_snapshot(&config, before.name, admin.clone())
+            .await
+            .unwrap();
+        // sessions are not rolled back, so the token still works
+        assert_eq!(titles(&repo, &token).await, ["Frieren", "Mushishi"]);
+        let search = repo
+            .search_banners(
+                "frieren".into(),
+                SearchMode::FullText,
+                None,
+                10,
+                None,
+                token.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(search.items.len(), 1);
+
+        repo.restore_snapshot(&config, after.name, admin.clone())
+            .await
+            .unwrap();
+        assert_eq!(titles(&repo, &token).await, ["Mushi-shi"]);
+    }
+
+    #[tokio::test]
+    async fn snapshots_from_another_schema_version_are_refused() {
+        let (dir, repo) = test_repo().await;
+        let config = snapshot_config(&dir);
+        let admin = login(&repo, "admin", "adminpassword").await;
+
+        let snapshot = repo.take_snapshot(&config, admin.clone()).await.unwrap();
+        let path = config.dir.join(&snapshot.name);
+        let mut connection = repo.database.acquire().await.unwrap();
+        sqlx::query("ATTACH DATABASE ? AS old")
+            .bind(path.to_string_lossy().into_owned())
+            .execute(&mut *connection)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            DELETE FROM old._sqlx_migrations
+            WHERE version = (SELECT MAX(version) FROM old._sqlx_migrations)
+            "#,
+        )
+        .execute(&mut *connection)
+        .await
+        .unwrap();
+        sqlx::query("DETACH DATABASE old")
+            .execute(&mut *connection)
+            .await
+            .unwrap();
+        drop(connection);
+
+        let listed = repo.list_snapshots(&config, admin.clone()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(!listed[0].compatible);
+
+        assert!(matches!(
+            repo.restore_snapshot(&config, snapshot.name, admin.clone())
+                .await,
+            Err(RepoError::Validation { .. })
+        ));
+        // refused before the current state was snapshotted
+        assert_eq!(repo.list_snapshots(&config, admin).await.unwrap().len(), 1);
+    }
+
+    fn change(method: &str, data: serde_json::Value, minute: u32, base: Option<i64>) -> SyncChange {
+        serde_json::from_value(json!({
+            "method": method,
+            "data": data,
+            "timestamp": format!("2026-10-18T10:{minute:02}:00Z"),
+            "base_version": base,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sync_skips_edits_made_against_an_old_version() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        let banner_id = repo
+            .add_banner(banner("Frieren", "Friday"), token.clone())
+            .await
+            .unwrap();
+        let cached = find(&repo, &token, "Frieren").await.version;
+
+        // edited on another device after this one went offline
+        let notes = BannerPatch {
+            notes: Some("from the other device".into()),
+            ..Default::default()
+        };
+        repo.update_banner(banner_id, notes, token.clone())
+            .await
+            .unwrap();
+        let current = find(&repo, &token, "Frieren").await.version;
+
+        let patch = |episodes: u32| {
+            let patch = json!({ "current_episodes": episodes });
+            json!({ "bannerId": banner_id, "patch": patch })
+        };
+        let report = repo
+            .sync_changes(
+                vec![
+                    change("update_banner", patch(5), 1, Some(cached)),
+                    change("update_banner", patch(6), 2, Some(current)),
+                    // queued right after the one before, against the same cached version
+                    change("update_banner", patch(7), 3, Some(current)),
+                    change(
+                        "rename_banner",
+                        json!({ "bannerId": banner_id, "title": "Sousou no Frieren" }),
+                        4,
+                        None,
+                    ),
+                ],
+                token.clone(),
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            &report.outcomes[0],
+            SyncOutcome::Conflict { banner }
+                if banner.version == current && banner.current_episodes == 1
+        ));
+        assert!(report.outcomes[1..]
+            .iter()
+            .all(|outcome| matches!(outcome, SyncOutcome::Applied { .. })));
+
+        let synced = &report.banners[0];
+        assert_eq!(synced.title, "Sousou no Frieren");
+        assert_eq!(synced.current_episodes, 7);
+        assert_eq!(synced.notes, "from the other device");
+
+        // once the batch is through, its base version is stale like any other
+        let replay = repo
+            .sync_changes(
+                vec![change("update_banner", patch(8), 5, Some(current))],
+                token.clone(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(replay.outcomes[0], SyncOutcome::Conflict { .. }));
+    }
+
+    #[tokio::test]
+    async fn sync_resolves_banners_added_offline_and_reports_failures() {
+        let (_dir, repo) = test_repo().await;
+        let token = login(&repo, "bobross", "pass").await;
+
+        let offline = json!({
+            "banner_id": -1,
+            "title": "Mushishi",
+            "release_day": "Sunday",
+            "release_time": "01:30",
+            "current_episodes": 0,
+            "total_episodes": 26,
+        });
+        let report = repo
+            .sync_changes(
+                vec![
+                    // sent out of order, applied by timestamp
+                    change(
+                        "set_banner_status",
+                        json!({ "bannerId": -1, "status": "OnHold" }),
+                        2,
+                        None,
+                    ),
+                    change("add_banner", json!({ "banner": offline }), 1, None),
+                    change(
+                        "rename_banner",
+                        json!({ "bannerId": -2, "title": "Nowhere" }),
+                        3,
+                        None,
+                    ),
+                    change("delete_banner", json!({ "bannerId": 99999 }), 4, None),
+                    change(
+                        "rename_banner",
+                        json!({ "bannerId": 99999, "title": "Gone" }),
+                        5,
+                        None,
+                    ),
+                ],
+                token.clone(),
+            )
+            .await
+            .unwrap();
+
+        let SyncOutcome::Applied { banner_id, .. } = report.outcomes[1] else {
+            panic!("the offline banner was not added");
+        };
+        assert!(banner_id > 0);
+        assert!(matches!(
+            report.outcomes[0],
+            SyncOutcome::Applied { banner_id: id, .. } if id == banner_id
+        ));
+        assert!(matches!(
+            report.outcomes[2],
+            SyncOutcome::Failed {
+                error: RepoError::NotFound
+            }
+        ));
+        // deleting what is already gone leaves the same result
+        assert!(matches!(
+            report.outcomes[3],
+            SyncOutcome::Applied { version: None, .. }
+        ));
+        assert!(matches!(
+            report.outcomes[4],
+            SyncOutcome::Failed {
+                error: RepoError::NotFound
+            }
+        ));
+
+        assert_eq!(report.banners.len(), 1);
+        assert_eq!(report.banners[0].status, WatchStatus::OnHold);
     }
 }
@@ -0,0 +1,113 @@
+use serde::Deserialize;
+use std::{
+    env, fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::snapshot::SnapshotConfig;
+
+pub const DB_PATH_FLAG: &str = "--db-path";
+pub const DB_PATH_ENV: &str = "OUR_ANIME_LIST_DB";
+
+// must match the identifier in tauri.conf.json so the fallback lands in Tauri's app data dir
+const APP_IDENTIFIER: &str = "com.our-anime-list.app";
+const CONFIG_FILE_NAME: &str = "config.json";
+const DB_FILE_NAME: &str = "sqlite.db";
+
+// snapshots live next to the database unless the config says otherwise
+const SNAPSHOT_DIR_NAME: &str = "snapshots";
+const DEFAULT_SNAPSHOT_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_SNAPSHOT_KEEP: usize = 5;
+
+#[derive(Deserialize, Default)]
+struct AppConfig {
+    database_path: Option<PathBuf>,
+    snapshot_dir: Option<PathBuf>,
+    // 0 turns scheduled snapshots off
+    snapshot_interval_minutes: Option<u64>,
+    snapshot_keep: Option<usize>,
+}
+
+pub fn resolve_db_path() -> Result<PathBuf, Error> {
+    if let Some(path) = db_path_from_args(env::args().skip(1)) {
+        return Ok(path);
+    }
+
+    if let Some(path) = env::var_os(DB_PATH_ENV).filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    if let Some(path) = db_path_from_config()? {
+        return Ok(path);
+    }
+
+    let data_dir = dirs::data_dir().ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            "could not locate the app data directory",
+        )
+    })?;
+
+    Ok(data_dir.join(APP_IDENTIFIER).join(DB_FILE_NAME))
+}
+
+fn db_path_from_args(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == DB_PATH_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+
+        if let Some(path) = arg.strip_prefix(&format!("{DB_PATH_FLAG}=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    None
+}
+
+pub fn resolve_snapshot_config(db_path: &Path) -> Result<SnapshotConfig, Error> {
+    let config = read_config()?.unwrap_or_default();
+
+    let dir = config.snapshot_dir.unwrap_or_else(|| {
+        db_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(SNAPSHOT_DIR_NAME)
+    });
+
+    let interval = match config
+        .snapshot_interval_minutes
+        .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_MINUTES)
+    {
+        0 => None,
+        minutes => Some(Duration::from_secs(minutes * 60)),
+    };
+
+    Ok(SnapshotConfig {
+        dir,
+        interval,
+        keep: config.snapshot_keep.unwrap_or(DEFAULT_SNAPSHOT_KEEP).max(1),
+    })
+}
+
+fn db_path_from_config() -> Result<Option<PathBuf>, Error> {
+    Ok(read_config()?.and_then(|config| config.database_path))
+}
+
+fn read_config() -> Result<Option<AppConfig>, Error> {
+    let config_path = match dirs::config_dir() {
+        Some(dir) => dir.join(APP_IDENTIFIER).join(CONFIG_FILE_NAME),
+        None => return Ok(None),
+    };
+
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let config: AppConfig = serde_json::from_str(&fs::read_to_string(&config_path)?)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    Ok(Some(config))
+}
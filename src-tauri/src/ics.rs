@@ -0,0 +1,305 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use time_tz::{Offset, OffsetDateTimeExt, TimeZone, Tz};
+
+use crate::banner::{Banner, WatchStatus};
+use crate::schedule::first_airing;
+use crate::validation::parse_time_zone;
+
+const PRODUCT_ID: &str = "-//Our Anime List//Airing Schedule//EN";
+
+// UIDs only depend on the banner, so calendar apps update events on re-import
+const UID_DOMAIN: &str = "our-anime-list.app";
+
+const EPISODE_DURATION: &str = "PT30M";
+
+// RFC 5545 limits content lines to 75 octets before folding
+const MAX_LINE_OCTETS: usize = 75;
+
+// time zones list their offset changes from the first event until this far past the
+// export; clients keep the last offset after that
+const TIME_ZONE_YEARS: i64 = 5;
+
+// banners whose release day, time or time zone cannot be read are left out of the
+// calendar and listed by title in skipped
+#[derive(Serialize)]
+pub struct IcsExport {
+    pub ics: String,
+    pub skipped: Vec<String>,
+}
+
+pub fn banners_to_ics(banners: &[Banner], now: OffsetDateTime) -> IcsExport {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        format!("PRODID:{PRODUCT_ID}"),
+        String::from("CALSCALE:GREGORIAN"),
+        String::from("METHOD:PUBLISH"),
+    ];
+
+    let mut events = vec![];
+    let mut skipped = vec![];
+    // the earliest event in each time zone, which its definition has to cover
+    let mut time_zones: BTreeMap<&str, (&Tz, OffsetDateTime)> = BTreeMap::new();
+
+    for banner in banners {
+        if banner.status == WatchStatus::Dropped {
+            continue;
+        }
+
+        // a total of 0 means the episode count is not known yet, so the series never ends
+        let rule = match banner.total_episodes {
+            0 => String::from("RRULE:FREQ=WEEKLY"),
+            total if banner.current_episodes >= total => continue,
+            total => format!("RRULE:FREQ=WEEKLY;COUNT={total}"),
+        };
+
+        // the series starts at its first episode rather than the next one, so exports
+        // agree on DTSTART and the rule covers the rest; weekly rules repeat the local
+        // time, so daylight saving changes keep the hour
+        let airing = parse_time_zone(&banner.time_zone)
+            .and_then(|time_zone| Ok((time_zone, first_airing(banner, now)?)));
+        let (time_zone, start) = match airing {
+            Ok(airing) => airing,
+            Err(_) => {
+                skipped.push(banner.title.clone());
+                continue;
+            }
+        };
+        time_zones
+            .entry(time_zone.name())
+            .and_modify(|(_, from)| *from = start.min(*from))
+            .or_insert((time_zone, start));
+
+        events.extend([
+            String::from("BEGIN:VEVENT"),
+            format!("UID:banner-{}@{UID_DOMAIN}", banner.banner_id),
+            format!("DTSTAMP:{}", format_utc(now)),
+            // every edit bumps the banner's version, so clients replace older copies
+            format!("SEQUENCE:{}", banner.version),
+            format!(
+                "DTSTART;TZID={}:{}",
+                time_zone.name(),
+                format_local(start.to_timezone(time_zone))
+            ),
+            format!("DURATION:{EPISODE_DURATION}"),
+            rule,
+            format!("SUMMARY:{}", escape_text(&banner.title)),
+            format!("DESCRIPTION:{}", escape_text(&describe_episodes(banner))),
+            String::from("END:VEVENT"),
+        ]);
+    }
+
+    for (time_zone, from) in time_zones.into_values() {
+        lines.extend(time_zone_lines(time_zone, from, now));
+    }
+
+    lines.extend(events);
+    lines.push(String::from("END:VCALENDAR"));
+
+    IcsExport {
+        ics: lines.iter().map(|line| fold_line(line)).collect(),
+        skipped,
+    }
+}
+
+fn describe_episodes(banner: &Banner) -> String {
+    match banner.total_episodes {
+        0 => format!("Next episode: {}", banner.current_episodes + 1),
+        total => format!("Episodes {} to {total}", banner.current_episodes + 1),
+    }
+}
+
+// DATE-TIME in UTC form, e.g. 20261018T150000Z
+fn format_utc(date_time: OffsetDateTime) -> String {
+    format!("{}Z", format_local(date_time.to_offset(UtcOffset::UTC)))
+}
+
+// DATE-TIME in local form, e.g. 20261019T000000, for use with a TZID
+fn format_local(date_time: OffsetDateTime) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        date_time.year(),
+        u8::from(date_time.month()),
+        date_time.day(),
+        date_time.hour(),
+        date_time.minute(),
+        date_time.second()
+    )
+}
+
+// UTC-OFFSET, e.g. +0900
+fn format_offset(offset: UtcOffset) -> String {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+
+    format!("{sign}{:02}{:02}", hours.abs(), minutes.abs())
+}
+
+// a VTIMEZONE with the offset in force at `from` and every change until
+// TIME_ZONE_YEARS after now; changes are found day by day, then to the second
+fn time_zone_lines(time_zone: &Tz, from: OffsetDateTime, now: OffsetDateTime) -> Vec<String> {
+    let offset_at = |instant: OffsetDateTime| time_zone.get_offset_utc(&instant).to_utc();
+
+    let observance = |instant: OffsetDateTime, offset_from: UtcOffset| {
+        let offset = time_zone.get_offset_utc(&instant);
+        let component = if offset.is_dst() {
+            "DAYLIGHT"
+        } else {
+            "STANDARD"
+        };
+
+        [
+            format!("BEGIN:{component}"),
+            format!("DTSTART:{}", format_local(instant.to_offset(offset_from))),
+            format!("TZOFFSETFROM:{}", format_offset(offset_from)),
+            format!("TZOFFSETTO:{}", format_offset(offset.to_utc())),
+            format!("TZNAME:{}", offset.name()),
+            format!("END:{component}"),
+        ]
+    };
+
+    let mut lines = vec![
+        String::from("BEGIN:VTIMEZONE"),
+        format!("TZID:{}", time_zone.name()),
+    ];
+
+    let from = PrimitiveDateTime::new(from.date(), Time::MIDNIGHT).assume_utc();
+    lines.extend(observance(from, offset_at(from)));
+
+    let mut day = from;
+    while day < now + Duration::days(365 * TIME_ZONE_YEARS) {
+        let next_day = day + Duration::DAY;
+
+        if offset_at(day) != offset_at(next_day) {
+            let (mut before, mut after) = (day, next_day);
+            while after - before > Duration::SECOND {
+                let middle = before + (after - before) / 2;
+                if offset_at(middle) == offset_at(day) {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+
+            lines.extend(observance(after, offset_at(day)));
+        }
+
+        day = next_day;
+    }
+
+    lines.push(String::from("END:VTIMEZONE"));
+    lines
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// continuation lines start with a space, which counts towards their 75 octets,
+// and a fold never splits a multi-byte character
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut line_octets = 0;
+
+    for c in line.chars() {
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            line_octets = 1;
+        }
+
+        folded.push(c);
+        line_octets += c.len_utf8();
+    }
+
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::format_description::well_known::Rfc3339;
+
+    fn banner(title: &str, current_episodes: u32, total_episodes: u32) -> Banner {
+        serde_json::from_value(json!({
+            "banner_id": 7,
+            "title": title,
+            "release_day": "Monday",
+            "release_time": "12:00",
+            "time_zone": "Asia/Tokyo",
+            "current_episodes": current_episodes,
+            "total_episodes": total_episodes,
+        }))
+        .unwrap()
+    }
+
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::parse("2026-10-18T00:00:00Z", &Rfc3339).unwrap()
+    }
+
+    // the unfolded lines of the banner's event
+    fn event(banner: &Banner) -> Vec<String> {
+        let export = banners_to_ics(std::slice::from_ref(banner), now());
+        export
+            .ics
+            .replace("\r\n ", "")
+            .split("\r\n")
+            .skip_while(|line| *line != "BEGIN:VEVENT")
+            .take_while(|line| *line != "END:VEVENT")
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn folded_lines_stay_within_75_octets_without_splitting_characters() {
+        assert_eq!(fold_line("SUMMARY:short"), "SUMMARY:short\r\n");
+
+        let line = format!("SUMMARY:{}", "葬送のフリーレン".repeat(10));
+        let folded = fold_line(&line);
+
+        assert!(folded.ends_with("\r\n"));
+        let parts: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(parts.len() > 1);
+        assert!(parts.iter().all(|part| part.len() <= MAX_LINE_OCTETS));
+        assert!(parts[1..].iter().all(|part| part.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), line);
+    }
+
+    #[test]
+    fn series_start_at_their_first_episode_and_count_every_episode() {
+        let lines = event(&banner("Frieren", 3, 12));
+
+        // the next airing is Monday the 19th, so episode 1 aired three weeks earlier
+        assert!(lines.contains(&String::from("DTSTART;TZID=Asia/Tokyo:20260928T120000")));
+        assert!(lines.contains(&String::from("RRULE:FREQ=WEEKLY;COUNT=12")));
+        assert!(lines.contains(&String::from("UID:banner-7@our-anime-list.app")));
+
+        let ongoing = event(&banner("One Piece", 3, 0));
+        assert!(ongoing.contains(&String::from("RRULE:FREQ=WEEKLY")));
+        assert!(ongoing.contains(&String::from("DESCRIPTION:Next episode: 4")));
+    }
+
+    #[test]
+    fn finished_and_dropped_banners_have_no_events() {
+        let mut dropped = banner("Dropped", 3, 12);
+        dropped.status = WatchStatus::Dropped;
+
+        for banner in [banner("Finished", 12, 12), dropped] {
+            let export = banners_to_ics(std::slice::from_ref(&banner), now());
+            assert!(!export.ics.contains("BEGIN:VEVENT"));
+            assert!(export.skipped.is_empty());
+        }
+    }
+
+    #[test]
+    fn text_escapes_separators_and_newlines() {
+        assert_eq!(escape_text("a,b;c\\d\r\ne\nf"), r"a\,b\;c\\d\ne\nf");
+    }
+}
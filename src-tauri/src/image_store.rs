@@ -0,0 +1,203 @@
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat, ImageReader,
+};
+use sha2::{Digest, Sha256};
+use sqlx::{Sqlite, Transaction};
+use std::io::Cursor;
+
+use crate::repo_error::RepoError;
+
+pub const MAX_IMAGE_DIMENSION: u32 = 1600;
+pub const THUMBNAIL_DIMENSION: u32 = 320;
+
+const JPEG_QUALITY: u8 = 90;
+const THUMBNAIL_QUALITY: u8 = 80;
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_MAGIC: &[u8] = b"\xff\xd8\xff";
+const RIFF_MAGIC: &[u8] = b"RIFF";
+const WEBP_MAGIC: &[u8] = b"WEBP";
+
+pub fn image_hash(image_binary: &[u8]) -> String {
+    Sha256::digest(image_binary)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn image_content_type(image_binary: &[u8]) -> Option<&'static str> {
+    if image_binary.starts_with(PNG_MAGIC) {
+        return Some("image/png");
+    }
+
+    if image_binary.starts_with(JPEG_MAGIC) {
+        return Some("image/jpeg");
+    }
+
+    if image_binary.starts_with(RIFF_MAGIC) && image_binary.get(8..12) == Some(WEBP_MAGIC) {
+        return Some("image/webp");
+    }
+
+    None
+}
+
+pub struct ProcessedImage {
+    pub binary: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+}
+
+// an empty upload means the banner has no cover, so there is nothing to process
+pub async fn process_upload(image_binary: Vec<u8>) -> Result<Option<ProcessedImage>, RepoError> {
+    if image_binary.is_empty() {
+        return Ok(None);
+    }
+
+    tokio::task::spawn_blocking(move || process_image(&image_binary))
+        .await
+        .map_err(|e| RepoError::database(e.to_string()))?
+        .map(Some)
+}
+
+pub fn process_image(image_binary: &[u8]) -> Result<ProcessedImage, RepoError> {
+    let invalid = |reason: String| RepoError::validation("image_binary", reason);
+
+    let reader = ImageReader::new(Cursor::new(image_binary))
+        .with_guessed_format()
+        .map_err(|e| invalid(e.to_string()))?;
+
+    if !matches!(
+        reader.format(),
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)
+    ) {
+        return Err(invalid(String::from("must be a PNG, JPEG or WebP image")));
+    }
+
+    let image = reader.decode().map_err(|e| invalid(e.to_string()))?;
+
+    // every cover is stored as a JPEG, whatever its size, so none keeps the metadata
+    // of its upload and covers are all served as one type
+    let binary = if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        let resized = image.resize(
+            MAX_IMAGE_DIMENSION,
+            MAX_IMAGE_DIMENSION,
+            FilterType::Lanczos3,
+        );
+        encode_jpeg(&resized, JPEG_QUALITY)?
+    } else {
+        encode_jpeg(&image, JPEG_QUALITY)?
+    };
+
+    let thumbnail = encode_jpeg(
+        &image.thumbnail(THUMBNAIL_DIMENSION, THUMBNAIL_DIMENSION),
+        THUMBNAIL_QUALITY,
+    )?;
+
+    Ok(ProcessedImage { binary, thumbnail })
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, RepoError> {
+    let mut buffer = vec![];
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality))
+        .map_err(|e| RepoError::database(e.to_string()))?;
+
+    Ok(buffer)
+}
+
+pub async fn store_image(
+    transaction: &mut Transaction<'_, Sqlite>,
+    image_binary: &[u8],
+    thumbnail_binary: Option<&[u8]>,
+) -> Result<String, RepoError> {
+    let hash = image_hash(image_binary);
+
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO Images (image_hash, image_binary, thumbnail_binary)
+        VALUES (?, ?, ?)"#,
+    )
+    .bind(&hash)
+    .bind(image_binary)
+    .bind(thumbnail_binary)
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(hash)
+}
+
+pub async fn remove_orphaned_images(
+    transaction: &mut Transaction<'_, Sqlite>,
+) -> Result<(), RepoError> {
+    sqlx::query(
+        r#"
+        DELETE FROM Images
+        WHERE image_hash NOT IN (
+            SELECT image_hash FROM Banners WHERE image_hash IS NOT NULL
+        )"#,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut buffer = Cursor::new(vec![]);
+        DynamicImage::new_rgba8(width, height)
+            .write_to(&mut buffer, ImageFormat::Png)
+            .unwrap();
+        buffer.into_inner()
+    }
+
+    fn dimensions(image_binary: &[u8]) -> (u32, u32) {
+        let image = image::load_from_memory(image_binary).unwrap();
+        (image.width(), image.height())
+    }
+
+    #[test]
+    fn covers_are_stored_as_jpegs_of_at_most_the_maximum_size() {
+        let small = process_image(&png(40, 60)).unwrap();
+        assert_eq!(image_content_type(&small.binary), Some("image/jpeg"));
+        assert_eq!(dimensions(&small.binary), (40, 60));
+
+        // the aspect ratio is kept
+        let large = process_image(&png(MAX_IMAGE_DIMENSION + 400, 500)).unwrap();
+        assert_eq!(image_content_type(&large.binary), Some("image/jpeg"));
+        assert_eq!(dimensions(&large.binary), (MAX_IMAGE_DIMENSION, 400));
+
+        // so the same upload is always stored under the same hash
+        assert_eq!(process_image(&png(40, 60)).unwrap().binary, small.binary);
+    }
+
+    #[test]
+    fn thumbnails_fit_within_their_dimension() {
+        let processed = process_image(&png(800, 1200)).unwrap();
+
+        assert_eq!(image_content_type(&processed.thumbnail), Some("image/jpeg"));
+        assert_eq!(
+            dimensions(&processed.thumbnail),
+            (THUMBNAIL_DIMENSION * 2 / 3, THUMBNAIL_DIMENSION)
+        );
+    }
+
+    #[test]
+    fn other_formats_and_broken_images_are_rejected() {
+        let mut truncated = png(40, 60);
+        truncated.truncate(truncated.len() / 2);
+
+        for image_binary in [
+            b"GIF89a\x01\x00\x01\x00\x00\x00\x00;".to_vec(),
+            b"not an image".to_vec(),
+            truncated,
+        ] {
+            assert!(matches!(
+                process_image(&image_binary),
+                Err(RepoError::Validation { .. })
+            ));
+        }
+    }
+}
@@ -1,131 +1,219 @@
-use banner::Banner;
+use backup::{MergePolicy, RestoreReport};
+use banner::{Banner, BannerPatch, WatchStatus};
+use banner_import::ImportReport;
+use banner_protocol::{handle_banner_request, BANNER_SCHEME};
+use banner_query::BannerQuery;
 use banner_repo::{BannerRepo, LoginResult};
-use sqlx::{migrate::MigrateDatabase, sqlite::SqliteConnectOptions, Error, Sqlite, SqlitePool};
+use ics::IcsExport;
+use image_store::store_image;
+use mal::MalExport;
+use page::Page;
+use password::{dummy_password_hash, hash_password, is_password_hash};
+use repo_error::RepoError;
+use schedule::{NextAiring, WeeklySchedule};
+use search::{SearchMode, SearchResult};
+use snapshot::{take_snapshot, SnapshotConfig, SnapshotInfo};
+use sqlx::{migrate::Migrator, sqlite::SqliteConnectOptions, Error, Sqlite, SqlitePool};
 use std::collections::HashMap;
-use std::{env, fs, path::Path, str::FromStr};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use std::{thread, vec};
-use tauri::Emitter;
+use sync::{SyncChange, SyncReport};
+use tauri::{Emitter, Manager};
 
+pub mod backup;
 pub mod banner;
+pub mod banner_csv;
+pub mod banner_import;
+pub mod banner_protocol;
+pub mod banner_query;
 pub mod banner_repo;
+pub mod db_config;
+pub mod ics;
+pub mod image_store;
+pub mod mal;
+pub mod page;
+pub mod password;
+pub mod repo_error;
+pub mod schedule;
+pub mod search;
+pub mod session;
+pub mod snapshot;
+pub mod sync;
+pub mod validation;
 
 type RepoLock<'a> = tauri::State<'a, BannerRepo>;
+type SnapshotState<'a> = tauri::State<'a, SnapshotConfig>;
 
-const DB_DIR_NAME: &str = "database";
+static MIGRATOR: Migrator = sqlx::migrate!();
 
-const DB_INIT: &str = r#"
-        CREATE TABLE IF NOT EXISTS Users (
-            user_type INTEGER NOT NULL,
-            password TEXT NOT NULL,
-            user_name TEXT PRIMARY KEY
-        );
+// 0013_drop_inline_images.sql
+const DROP_INLINE_IMAGES_VERSION: i64 = 13;
 
-        CREATE TABLE IF NOT EXISTS Banners (
-            image_binary BLOB NOT NULL,
-            title TEXT,
-            release_day TEXT NOT NULL,
-            release_time TEXT NOT NULL,
-            current_episodes INTEGER NOT NULL,
-            total_episodes INTEGER NOT NULL,
-            user_name TEXT REFERENCES Users(user_name),
-            PRIMARY KEY (user_name, title)
-        );
+const DEFAULT_USERS: [(u32, &str, &str); 2] =
+    [(0, "adminpassword", "admin"), (1, "pass", "bobross")];
 
-        CREATE TABLE IF NOT EXISTS Logs (
-            user_name TEXT REFERENCES Users(user_name),
-            log_id INTEGER PRIMARY KEY AUTOINCREMENT,
-            action TEXT NOT NULL,
-            timestamp TEXT NOT NULL
-        );
+const MONITOR_INTERVAL: u64 = 10;
 
-        CREATE TABLE IF NOT EXISTS SuspiciousUsers (
-            user_name TEXT REFERENCES Users(user_name),
-            PRIMARY KEY (user_name)
-        );
-        "#;
+const SUS_ACTION_COUNT: usize = 10;
 
-const ADMIN_INSERT: &str = r#"
-        INSERT INTO Users (
-                user_type,
-                password,
-                user_name
-        ) VALUES (0, 'adminpassword', 'admin');
+pub async fn set_up_database(db_path: &Path) -> Result<sqlx::Pool<Sqlite>, Error> {
+    let db_existed = db_path.exists();
 
-        INSERT INTO Users (
-            user_type,
-            password,
-            user_name
-        ) VALUES (1, 'pass', 'bobross');
-"#;
+    if let Some(db_dir) = db_path.parent() {
+        fs::create_dir_all(db_dir)?;
+    }
 
-const MONITOR_INTERVAL: u64 = 10;
+    let options = SqliteConnectOptions::new()
+        .filename(db_path)
+        .create_if_missing(true)
+        .pragma("foreign_keys", "ON");
 
-const SUS_ACTION_COUNT: usize = 10;
+    let db: sqlx::Pool<Sqlite> = SqlitePool::connect_with(options).await?;
+    run_migrations(&db).await?;
 
-const PATH_TO_DATABASE: &str = "C:\\Users\\x8hnc\\Desktop\\mpp_labs";
-fn get_db_creation_path() -> Result<String, std::io::Error> {
-    #[cfg(not(debug_assertions))]
-    let current_dir = env::current_dir()?.display().to_string();
+    if !db_existed {
+        insert_default_users(&db).await?;
+    }
 
-    #[cfg(debug_assertions)]
-    let current_dir = PATH_TO_DATABASE.to_owned();
+    rehash_plaintext_passwords(&db).await?;
+    // computed up front, so the first login for an unknown user is no slower than the rest
+    dummy_password_hash();
 
-    Ok(format!("sqlite://{current_dir}/{DB_DIR_NAME}/sqlite.db"))
+    Ok(db)
 }
 
-fn set_up_db_directory() -> Result<(), std::io::Error> {
-    #[cfg(not(debug_assertions))]
-    let current_dir = env::current_dir()?.display().to_string();
+async fn insert_default_users(db: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
+    for (user_type, password, user_name) in DEFAULT_USERS {
+        let password_hash = hash_password(password).map_err(Error::Protocol)?;
 
-    #[cfg(debug_assertions)]
-    let current_dir = PATH_TO_DATABASE.to_owned();
+        sqlx::query(
+            r#"
+            INSERT INTO Users (
+                user_type,
+                password,
+                user_name
+            ) VALUES (?, ?, ?)"#,
+        )
+        .bind(user_type)
+        .bind(password_hash)
+        .bind(user_name)
+        .execute(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+// all or nothing, so a failure part way cannot leave some rows hashed and others not
+async fn rehash_plaintext_passwords(db: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
+    let mut transaction = db.begin().await?;
+    let users: Vec<(String, String)> = sqlx::query_as("SELECT user_name, password FROM Users")
+        .fetch_all(&mut transaction)
+        .await?;
+
+    for (user_name, password) in users {
+        if is_password_hash(&password) {
+            continue;
+        }
 
-    let db_path = format!("{current_dir}/{DB_DIR_NAME}");
+        let password_hash = hash_password(&password).map_err(Error::Protocol)?;
 
-    if !Path::new(&db_path).exists() {
-        fs::create_dir(db_path)?;
+        sqlx::query("UPDATE Users SET password = ? WHERE user_name = ?")
+            .bind(password_hash)
+            .bind(user_name)
+            .execute(&mut transaction)
+            .await?;
     }
 
+    transaction.commit().await?;
+
     Ok(())
 }
 
-pub async fn set_up_database() -> Result<sqlx::Pool<Sqlite>, Error> {
-    let db_url = get_db_creation_path().map_err(sqlx::Error::Io)?;
-    let mut db_existed = true;
+// covers move from Banners into Images in Rust, since SQLite cannot hash them, so the
+// migration dropping the inline column waits until they have been copied
+async fn run_migrations(db: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
+    let before_drop = Migrator {
+        migrations: MIGRATOR
+            .iter()
+            .filter(|migration| migration.version < DROP_INLINE_IMAGES_VERSION)
+            .cloned()
+            .collect::<Vec<_>>()
+            .into(),
+        // the database may already be past the drop
+        ignore_missing: true,
+        locking: MIGRATOR.locking,
+    };
+    before_drop.run(db).await?;
+
+    let images_moved: bool =
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM _sqlx_migrations WHERE version = ?)")
+            .bind(DROP_INLINE_IMAGES_VERSION)
+            .fetch_one(db)
+            .await?;
 
-    if !Sqlite::database_exists(&db_url).await.unwrap_or(false) {
-        set_up_db_directory().map_err(sqlx::Error::Io)?;
-        Sqlite::create_database(&db_url).await?;
-        db_existed = false;
+    if !images_moved {
+        move_banner_images_to_store(db).await?;
     }
 
-    let options = SqliteConnectOptions::from_str(&db_url)?
-        .create_if_missing(true)
-        .pragma("foreign_keys", "ON");
+    MIGRATOR.run(db).await?;
 
-    let db: sqlx::Pool<Sqlite> = SqlitePool::connect_with(options).await?;
-    sqlx::query(DB_INIT).execute(&db).await?;
+    Ok(())
+}
 
-    if !db_existed {
-        sqlx::query(ADMIN_INSERT).execute(&db).await?;
+// only banners without an image_hash are copied, so an interrupted run can simply repeat
+async fn move_banner_images_to_store(db: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
+    let mut transaction = db.begin().await?;
+    let banners: Vec<(i64, Vec<u8>)> =
+        sqlx::query_as("SELECT banner_id, image_binary FROM Banners WHERE image_hash IS NULL")
+            .fetch_all(&mut transaction)
+            .await?;
+
+    for (banner_id, image_binary) in banners {
+        if image_binary.is_empty() {
+            continue;
+        }
+
+        let image_hash = store_image(&mut transaction, &image_binary, None)
+            .await
+            .map_err(|e| Error::Protocol(e.to_string()))?;
+
+        sqlx::query("UPDATE Banners SET image_hash = ? WHERE banner_id = ?")
+            .bind(image_hash)
+            .bind(banner_id)
+            .execute(&mut transaction)
+            .await?;
     }
 
-    Ok(db)
+    transaction.commit().await?;
+
+    Ok(())
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn simulate_attack(userName: String, repo: RepoLock<'_>) -> Result<(), String> {
+async fn simulate_attack(token: String, repo: RepoLock<'_>) -> Result<(), RepoError> {
     for i in 0..100 {
         let banner = Banner {
+            banner_id: 0,
             image_binary: vec![],
+            image_hash: None,
             title: i.to_string(),
+            alternate_titles: String::new(),
+            notes: String::new(),
             release_day: String::from("Monday"),
             release_time: String::from("10:00"),
+            time_zone: String::from(schedule::DEFAULT_TIME_ZONE),
             current_episodes: 1,
             total_episodes: 10,
+            status: WatchStatus::Watching,
+            mal_id: None,
+            version: 0,
         };
-        repo.add_banner(banner, userName.clone()).await?;
+        repo.add_banner(banner, token.clone()).await?;
     }
 
     Ok(())
@@ -137,19 +225,26 @@ async fn login(
     userName: String,
     password: String,
     repo: RepoLock<'_>,
-) -> Result<LoginResult, String> {
+) -> Result<LoginResult, RepoError> {
     repo.login(userName, password).await
 }
 
+#[tauri::command]
+async fn logout(token: String, repo: RepoLock<'_>) -> Result<(), RepoError> {
+    repo.logout(token).await
+}
+
 #[tauri::command]
 #[allow(non_snake_case)]
 async fn register_user(
     userName: String,
     password: String,
-    isAdmin: bool,
+    isAdmin: Option<bool>,
+    token: Option<String>,
     repo: RepoLock<'_>,
-) -> Result<bool, String> {
-    repo.register_user(userName, password, isAdmin).await
+) -> Result<(), RepoError> {
+    repo.register_user(userName, password, isAdmin.unwrap_or(false), token)
+        .await
 }
 
 #[tauri::command]
@@ -164,104 +259,253 @@ fn check_network() -> bool {
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn add_banner(banner: Banner, userName: String, repo: RepoLock<'_>) -> Result<(), String> {
-    repo.add_banner(banner, userName).await
+async fn add_banner(banner: Banner, token: String, repo: RepoLock<'_>) -> Result<i64, RepoError> {
+    repo.add_banner(banner, token).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn delete_banner(bannerId: i64, token: String, repo: RepoLock<'_>) -> Result<(), RepoError> {
+    repo.delete_banner(bannerId, token).await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn delete_banner(title: String, userName: String, repo: RepoLock<'_>) -> Result<(), String> {
-    repo.delete_banner(title, userName).await
+async fn rename_banner(
+    bannerId: i64,
+    title: String,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<(), RepoError> {
+    repo.rename_banner(bannerId, title, token).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_banner_image(
+    bannerId: i64,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<Vec<u8>, RepoError> {
+    repo.get_banner_image(bannerId, token).await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
 async fn search_banners(
     query: String,
+    mode: Option<SearchMode>,
+    status: Option<WatchStatus>,
     pageSize: usize,
-    pageCount: usize,
-    userName: String,
+    cursor: Option<String>,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<Vec<Banner>, String> {
-    repo.search_banners(query, pageSize, pageCount, userName)
-        .await
+) -> Result<Page<SearchResult>, RepoError> {
+    repo.search_banners(
+        query,
+        mode.unwrap_or_default(),
+        status,
+        pageSize,
+        cursor,
+        token,
+    )
+    .await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
 async fn get_sorted_banners_release_day(
     pageSize: usize,
-    pageCount: usize,
-    userName: String,
+    cursor: Option<String>,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<Vec<Banner>, String> {
-    repo.sort_banners_by_release_day(pageSize, pageCount, userName)
+) -> Result<Page<Banner>, RepoError> {
+    repo.sort_banners_by_release_day(pageSize, cursor, token)
         .await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn get_all_banners(userName: String, repo: RepoLock<'_>) -> Result<Vec<Banner>, String> {
-    repo.get_all_banners(userName).await
+async fn get_all_banners(token: String, repo: RepoLock<'_>) -> Result<Vec<Banner>, RepoError> {
+    repo.get_all_banners(token).await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn update_banner_current_episodes(
-    title: String,
-    currentEpisodes: u32,
-    userName: String,
+async fn set_banner_status(
+    bannerId: i64,
+    status: WatchStatus,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<(), String> {
-    repo.update_banner_current_episodes(title, currentEpisodes, userName)
-        .await
+) -> Result<(), RepoError> {
+    repo.set_banner_status(bannerId, status, token).await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn update_banner_total_episodes(
-    title: String,
-    totalEpisodes: u32,
-    userName: String,
+async fn fill_missing_time_zones(
+    timeZone: String,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<(), String> {
-    repo.update_banner_total_episodes(title, totalEpisodes, userName)
-        .await
+) -> Result<u64, RepoError> {
+    repo.fill_missing_time_zones(timeZone, token).await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn update_banner_release_day(
-    title: String,
-    releaseDay: String,
-    userName: String,
+async fn update_banner(
+    bannerId: i64,
+    patch: BannerPatch,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<(), String> {
-    repo.update_banner_release_day(title, releaseDay, userName)
-        .await
+) -> Result<(), RepoError> {
+    repo.update_banner(bannerId, patch, token).await
+}
+
+#[tauri::command]
+async fn next_airing(token: String, repo: RepoLock<'_>) -> Result<Vec<NextAiring>, RepoError> {
+    repo.next_airings(token).await
+}
+
+#[tauri::command]
+async fn export_ics(token: String, repo: RepoLock<'_>) -> Result<IcsExport, RepoError> {
+    repo.export_ics(token).await
+}
+
+#[tauri::command]
+async fn import_mal(
+    data: Vec<u8>,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<ImportReport, RepoError> {
+    repo.import_mal(data, token).await
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn update_banner_release_time(
-    title: String,
-    releaseTime: String,
-    userName: String,
+async fn import_csv(
+    data: Vec<u8>,
+    mapping: Option<HashMap<String, String>>,
+    dryRun: Option<bool>,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<(), String> {
-    repo.update_banner_release_time(title, releaseTime, userName)
+) -> Result<ImportReport, RepoError> {
+    repo.import_csv(
+        data,
+        mapping.unwrap_or_default(),
+        dryRun.unwrap_or(false),
+        token,
+    )
+    .await
+}
+
+#[tauri::command]
+async fn export_csv(token: String, repo: RepoLock<'_>) -> Result<String, RepoError> {
+    repo.export_csv(token).await
+}
+
+#[tauri::command]
+async fn create_backup(path: PathBuf, token: String, repo: RepoLock<'_>) -> Result<(), RepoError> {
+    repo.create_backup(path, token).await
+}
+
+#[tauri::command]
+async fn restore_backup(
+    path: PathBuf,
+    policy: Option<MergePolicy>,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<RestoreReport, RepoError> {
+    repo.restore_backup(path, policy.unwrap_or_default(), token)
         .await
 }
 
+#[tauri::command]
+async fn list_snapshots(
+    token: String,
+    repo: RepoLock<'_>,
+    snapshots: SnapshotState<'_>,
+) -> Result<Vec<SnapshotInfo>, RepoError> {
+    repo.list_snapshots(&snapshots, token).await
+}
+
+#[tauri::command]
+async fn create_snapshot(
+    token: String,
+    repo: RepoLock<'_>,
+    snapshots: SnapshotState<'_>,
+) -> Result<SnapshotInfo, RepoError> {
+    repo.take_snapshot(&snapshots, token).await
+}
+
+#[tauri::command]
+async fn restore_snapshot(
+    name: String,
+    token: String,
+    repo: RepoLock<'_>,
+    snapshots: SnapshotState<'_>,
+) -> Result<SnapshotInfo, RepoError> {
+    repo.restore_snapshot(&snapshots, name, token).await
+}
+
+#[tauri::command]
+async fn sync_changes(
+    changes: Vec<SyncChange>,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<SyncReport, RepoError> {
+    repo.sync_changes(changes, token).await
+}
+
+#[tauri::command]
+async fn export_mal(token: String, repo: RepoLock<'_>) -> Result<MalExport, RepoError> {
+    repo.export_mal(token).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_weekly_schedule(
+    weekStart: String,
+    timeZone: String,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<WeeklySchedule, RepoError> {
+    repo.get_weekly_schedule(weekStart, timeZone, token).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn query_banners(
+    query: BannerQuery,
+    pageSize: usize,
+    cursor: Option<String>,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<Page<Banner>, RepoError> {
+    repo.query_banners(query, pageSize, cursor, token).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_banner_tags(
+    bannerId: i64,
+    token: String,
+    repo: RepoLock<'_>,
+) -> Result<Vec<String>, RepoError> {
+    repo.get_banner_tags(bannerId, token).await
+}
+
 #[tauri::command]
 #[allow(non_snake_case)]
 async fn get_paged_banners(
+    status: Option<WatchStatus>,
     pageSize: usize,
-    pageCount: usize,
-    userName: String,
+    cursor: Option<String>,
+    token: String,
     repo: RepoLock<'_>,
-) -> Result<Vec<Banner>, String> {
-    repo.get_paged_banners(pageSize, pageCount, userName).await
+) -> Result<Page<Banner>, RepoError> {
+    repo.get_paged_banners(status, pageSize, cursor, token)
+        .await
 }
 
 async fn monitor_db(db: sqlx::Pool<Sqlite>, app_handle: tauri::AppHandle) {
@@ -323,8 +567,27 @@ fn notify_attack(user_name: &str, app_handle: &tauri::AppHandle) {
     app_handle.emit("attack_detected", user_name).unwrap();
 }
 
-pub fn run_app(db: sqlx::Pool<Sqlite>) {
+async fn snapshot_db(db: sqlx::Pool<Sqlite>, snapshots: SnapshotConfig) {
+    let Some(interval) = snapshots.interval else {
+        return;
+    };
+
+    let mut ticker = tokio::time::interval(interval);
+    // the first tick completes immediately, and startup already has a fresh database
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+
+        if let Err(e) = take_snapshot(&db, &snapshots).await {
+            eprintln!("Error taking snapshot: {:?}", e);
+        }
+    }
+}
+
+pub fn run_app(db: sqlx::Pool<Sqlite>, snapshots: SnapshotConfig) {
     let monitor_pool = db.clone();
+    let snapshot_pool = db.clone();
+    let snapshot_config = snapshots.clone();
 
     tauri::Builder::default()
         .setup(|app| {
@@ -333,30 +596,208 @@ pub fn run_app(db: sqlx::Pool<Sqlite>) {
             thread::spawn(move || {
                 let async_runtime = tokio::runtime::Runtime::new().unwrap();
                 async_runtime.block_on(async move {
-                    monitor_db(monitor_pool, app_handle).await;
+                    tokio::join!(
+                        monitor_db(monitor_pool, app_handle),
+                        snapshot_db(snapshot_pool, snapshot_config)
+                    );
                 })
             });
 
             Ok(())
         })
         .manage(BannerRepo::new(db))
+        .manage(snapshots)
+        .register_asynchronous_uri_scheme_protocol(BANNER_SCHEME, |context, request, responder| {
+            let app_handle = context.app_handle().clone();
+
+            tauri::async_runtime::spawn(async move {
+                let repo = app_handle.state::<BannerRepo>();
+                responder.respond(handle_banner_request(&repo, request).await);
+            });
+        })
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             add_banner,
             delete_banner,
+            rename_banner,
             search_banners,
+            get_banner_image,
             get_all_banners,
-            update_banner_current_episodes,
-            update_banner_total_episodes,
-            update_banner_release_day,
-            update_banner_release_time,
+            update_banner,
+            set_banner_status,
+            fill_missing_time_zones,
             get_sorted_banners_release_day,
             get_paged_banners,
+            query_banners,
+            get_banner_tags,
+            next_airing,
+            get_weekly_schedule,
+            export_ics,
+            import_mal,
+            export_mal,
+            import_csv,
+            export_csv,
+            create_backup,
+            restore_backup,
+            list_snapshots,
+            create_snapshot,
+            restore_snapshot,
+            sync_changes,
             check_network,
             register_user,
             login,
+            logout,
             simulate_attack
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the schema the app created for itself before it had migrations
+    const BASELINE_SCHEMA: &str = r#"
+        CREATE TABLE Users (
+            user_type INTEGER NOT NULL,
+            password TEXT NOT NULL,
+            user_name TEXT PRIMARY KEY
+        );
+
+        CREATE TABLE Banners (
+            image_binary BLOB NOT NULL,
+            title TEXT,
+            release_day TEXT NOT NULL,
+            release_time TEXT NOT NULL,
+            current_episodes INTEGER NOT NULL,
+            total_episodes INTEGER NOT NULL,
+            user_name TEXT REFERENCES Users(user_name),
+            PRIMARY KEY (user_name, title)
+        );
+
+        CREATE TABLE Logs (
+            user_name TEXT REFERENCES Users(user_name),
+            log_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            action TEXT NOT NULL,
+            timestamp TEXT NOT NULL
+        );
+
+        CREATE TABLE SuspiciousUsers (
+            user_name TEXT REFERENCES Users(user_name),
+            PRIMARY KEY (user_name)
+        );
+
+        INSERT INTO Users (user_type, password, user_name) VALUES
+            (0, 'rootpassword', 'root'),
+            (1, 'alicepassword', 'alice');
+
+        INSERT INTO Banners (
+            image_binary, title, release_day, release_time, current_episodes, total_episodes, user_name
+        ) VALUES
+            (x'010203', 'Frieren', ' friday', '23:00:00', 3, 28, 'alice'),
+            (x'040506', NULL, 'Monday', '10:00', 0, 12, 'alice'),
+            (x'0a0b0c', NULL, 'MONDAY', '9:30', 0, 12, 'alice'),
+            (x'070809', 'Mushishi', 'Sunday', '01:30', 26, 26, 'root'),
+            (x'', 'No Cover', 'Someday', 'noon', 1, 10, 'root');
+
+        INSERT INTO Logs (user_name, action, timestamp) VALUES
+            ('alice', 'add', '2024-01-01T00:00:00Z'),
+            ('root', 'delete', '2024-01-02T00:00:00Z'),
+            (NULL, 'login', '2024-01-03T00:00:00Z');
+    "#;
+
+    async fn count(db: &sqlx::Pool<Sqlite>, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn upgrading_a_baseline_database_keeps_every_row() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("sqlite.db");
+
+        let options = SqliteConnectOptions::new()
+            .filename(&db_path)
+            .create_if_missing(true);
+        let baseline = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::query(BASELINE_SCHEMA)
+            .execute(&baseline)
+            .await
+            .unwrap();
+        baseline.close().await;
+
+        let db = set_up_database(&db_path).await.unwrap();
+
+        assert_eq!(count(&db, "Users").await, 2);
+        assert_eq!(count(&db, "Banners").await, 5);
+        assert_eq!(count(&db, "Logs").await, 3);
+
+        let banners: Vec<(String, String, Option<Vec<u8>>)> = sqlx::query_as(
+            r#"
+            SELECT Banners.user_name, Banners.title, Images.image_binary
+            FROM Banners LEFT JOIN Images ON Images.image_hash = Banners.image_hash
+            ORDER BY Banners.user_name, Banners.title
+            "#,
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        let expected = [
+            ("alice", "Frieren", Some(vec![1, 2, 3])),
+            ("alice", "untitled-2", Some(vec![4, 5, 6])),
+            ("alice", "untitled-3", Some(vec![10, 11, 12])),
+            ("root", "Mushishi", Some(vec![7, 8, 9])),
+            ("root", "No Cover", None),
+        ]
+        .map(|(user_name, title, image)| (user_name.to_owned(), title.to_owned(), image));
+        assert_eq!(banners, expected);
+
+        // what cannot be read is kept as it was, and no banner has a time zone yet
+        let releases: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT release_day, release_time, time_zone FROM Banners ORDER BY user_name, title",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        let expected = [
+            ("Friday", "23:00"),
+            ("Monday", "10:00"),
+            ("Monday", "09:30"),
+            ("Sunday", "01:30"),
+            ("Someday", "noon"),
+        ]
+        .map(|(day, time)| (day.to_owned(), time.to_owned(), String::new()));
+        assert_eq!(releases, expected);
+
+        let actions: Vec<String> = sqlx::query_scalar("SELECT action FROM Logs ORDER BY log_id")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(actions, ["add", "delete", "login"]);
+
+        let passwords: Vec<String> = sqlx::query_scalar("SELECT password FROM Users")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert!(passwords.iter().all(|password| is_password_hash(password)));
+
+        let inline_images: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('Banners') WHERE name = 'image_binary'",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert!(!inline_images);
+
+        let version: i64 = sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        let latest = MIGRATOR.iter().map(|migration| migration.version).max();
+        assert_eq!(Some(version), latest);
+    }
+}
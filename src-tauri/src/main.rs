@@ -4,8 +4,24 @@ use our_anime_list_lib::*;
 
 #[tokio::main]
 async fn main() {
-    match set_up_database().await {
-        Ok(db) => run_app(db),
+    let db_path = match db_config::resolve_db_path() {
+        Ok(db_path) => db_path,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+
+    let snapshots = match db_config::resolve_snapshot_config(&db_path) {
+        Ok(snapshots) => snapshots,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+
+    match set_up_database(&db_path).await {
+        Ok(db) => run_app(db, snapshots),
         Err(error) => {
             eprintln!("{}", error);
             return;
@@ -0,0 +1,423 @@
+use flate2::read::GzDecoder;
+use quick_xml::{escape::escape, events::Event, Reader};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use time::Date;
+
+use crate::banner::{Banner, WatchStatus};
+use crate::banner_import::ImportIssue;
+use crate::repo_error::RepoError;
+use crate::schedule::DEFAULT_TIME_ZONE;
+use crate::validation::{parse_date, Weekday, MAX_IMPORT_BYTES};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// MyAnimeList exports carry no airing schedule, so imported banners air at noon
+// on the weekday the user started watching, or on the day of the import
+const IMPORTED_RELEASE_TIME: &str = "12:00";
+
+// MyAnimeList writes unknown dates as zeros
+const NO_DATE: &str = "0000-00-00";
+
+pub fn parse_mal_export(
+    data: &[u8],
+    today: Date,
+) -> Result<Vec<Result<Banner, ImportIssue>>, RepoError> {
+    let xml = decompress(data)?;
+    let entries = read_anime_entries(&xml)?;
+
+    Ok(entries
+        .iter()
+        .enumerate()
+        .map(|(index, fields)| entry_to_banner(fields, today, index))
+        .collect())
+}
+
+// MyAnimeList matches entries by series_animedb_id, so banners without a mal_id are
+// left out of the file and listed by title in skipped
+#[derive(Serialize)]
+pub struct MalExport {
+    pub xml: String,
+    pub skipped: Vec<String>,
+}
+
+pub fn banners_to_mal_xml(banners: &[Banner], user_name: &str) -> MalExport {
+    let exported: Vec<(i64, &Banner)> = banners
+        .iter()
+        .filter_map(|banner| Some((banner.mal_id?, banner)))
+        .collect();
+    let skipped = banners
+        .iter()
+        .filter(|banner| banner.mal_id.is_none())
+        .map(|banner| banner.title.clone())
+        .collect();
+
+    let count = |status: WatchStatus| exported.iter().filter(|(_, b)| b.status == status).count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n<myanimelist>\n");
+    xml.push_str("\t<myinfo>\n");
+    xml.push_str(&format!(
+        "\t\t<user_name>{}</user_name>\n",
+        escape(user_name)
+    ));
+    xml.push_str("\t\t<user_export_type>1</user_export_type>\n");
+    xml.push_str(&format!(
+        "\t\t<user_total_anime>{}</user_total_anime>\n",
+        exported.len()
+    ));
+    for (element, status) in [
+        ("user_total_watching", WatchStatus::Watching),
+        ("user_total_completed", WatchStatus::Completed),
+        ("user_total_onhold", WatchStatus::OnHold),
+        ("user_total_dropped", WatchStatus::Dropped),
+        ("user_total_plantowatch", WatchStatus::PlanToWatch),
+    ] {
+        xml.push_str(&format!("\t\t<{element}>{}</{element}>\n", count(status)));
+    }
+    xml.push_str("\t</myinfo>\n");
+
+    for (mal_id, banner) in exported {
+        xml.push_str("\t<anime>\n");
+        xml.push_str(&format!(
+            "\t\t<series_animedb_id>{mal_id}</series_animedb_id>\n"
+        ));
+        xml.push_str(&format!(
+            "\t\t<series_title>{}</series_title>\n",
+            cdata(&banner.title)
+        ));
+        xml.push_str("\t\t<series_type>TV</series_type>\n");
+        xml.push_str(&format!(
+            "\t\t<series_episodes>{}</series_episodes>\n",
+            banner.total_episodes
+        ));
+        xml.push_str("\t\t<my_id>0</my_id>\n");
+        xml.push_str(&format!(
+            "\t\t<my_watched_episodes>{}</my_watched_episodes>\n",
+            banner.current_episodes
+        ));
+        xml.push_str(&format!("\t\t<my_start_date>{NO_DATE}</my_start_date>\n"));
+        xml.push_str(&format!("\t\t<my_finish_date>{NO_DATE}</my_finish_date>\n"));
+        xml.push_str("\t\t<my_score>0</my_score>\n");
+        xml.push_str(&format!(
+            "\t\t<my_status>{}</my_status>\n",
+            status_name(banner.status)
+        ));
+        xml.push_str(&format!(
+            "\t\t<my_comments>{}</my_comments>\n",
+            cdata(&banner.notes)
+        ));
+        xml.push_str("\t\t<my_times_watched>0</my_times_watched>\n");
+        xml.push_str("\t\t<my_rewatching>0</my_rewatching>\n");
+        xml.push_str("\t\t<update_on_import>1</update_on_import>\n");
+        xml.push_str("\t</anime>\n");
+    }
+
+    xml.push_str("</myanimelist>\n");
+
+    MalExport { xml, skipped }
+}
+
+// accepts both the .xml.gz MyAnimeList hands out and an already extracted file
+fn decompress(data: &[u8]) -> Result<Vec<u8>, RepoError> {
+    let too_large =
+        || RepoError::validation("file", format!("must not exceed {MAX_IMPORT_BYTES} bytes"));
+
+    if data.len() > MAX_IMPORT_BYTES {
+        return Err(too_large());
+    }
+
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(data.to_vec());
+    }
+
+    let mut xml = vec![];
+    GzDecoder::new(data)
+        .take(MAX_IMPORT_BYTES as u64 + 1)
+        .read_to_end(&mut xml)
+        .map_err(|_| RepoError::validation("file", "is not a valid gzip archive"))?;
+
+    if xml.len() > MAX_IMPORT_BYTES {
+        return Err(too_large());
+    }
+
+    Ok(xml)
+}
+
+// collects the text of every child element of each <anime> element
+fn read_anime_entries(xml: &[u8]) -> Result<Vec<HashMap<String, String>>, RepoError> {
+    let invalid = || RepoError::validation("file", "is not a valid MyAnimeList export");
+
+    let mut reader = Reader::from_reader(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut buffer = vec![];
+    let mut entries = vec![];
+    let mut entry: Option<HashMap<String, String>> = None;
+    let mut element: Option<String> = None;
+    let mut found_root = false;
+
+    loop {
+        match reader.read_event_into(&mut buffer).map_err(|_| invalid())? {
+            Event::Start(start) => match start.name().as_ref() {
+                b"myanimelist" => found_root = true,
+                b"anime" => entry = Some(HashMap::new()),
+                name if entry.is_some() => {
+                    element = Some(String::from_utf8_lossy(name).into_owned());
+                }
+                _ => {}
+            },
+            Event::Text(text) => {
+                if let (Some(entry), Some(element)) = (&mut entry, &element) {
+                    let text = text.unescape().map_err(|_| invalid())?;
+                    entry.entry(element.clone()).or_default().push_str(&text);
+                }
+            }
+            Event::CData(text) => {
+                if let (Some(entry), Some(element)) = (&mut entry, &element) {
+                    let text =
+                        String::from_utf8(text.into_inner().into_owned()).map_err(|_| invalid())?;
+                    entry.entry(element.clone()).or_default().push_str(&text);
+                }
+            }
+            Event::End(end) => match end.name().as_ref() {
+                b"anime" => entries.extend(entry.take()),
+                _ => element = None,
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+
+        buffer.clear();
+    }
+
+    if !found_root {
+        return Err(invalid());
+    }
+
+    Ok(entries)
+}
+
+fn entry_to_banner(
+    fields: &HashMap<String, String>,
+    today: Date,
+    index: usize,
+) -> Result<Banner, ImportIssue> {
+    let field = |name: &str| fields.get(name).map(|value| value.trim()).unwrap_or("");
+
+    let title = match field("series_title") {
+        "" => format!("entry {}", index + 1),
+        title => title.to_owned(),
+    };
+
+    let issue = |error: RepoError| ImportIssue {
+        row: index + 1,
+        title: title.clone(),
+        error,
+    };
+
+    let number = |name: &'static str| -> Result<u32, ImportIssue> {
+        match field(name) {
+            "" => Ok(0),
+            value => value
+                .parse()
+                .map_err(|_| issue(RepoError::validation(name, "must be a whole number"))),
+        }
+    };
+
+    if field("series_title").is_empty() {
+        return Err(issue(RepoError::validation("series_title", "is missing")));
+    }
+
+    let watched_episodes = number("my_watched_episodes")?;
+    let series_episodes = number("series_episodes")?;
+    let status = parse_status(field("my_status")).map_err(issue)?;
+
+    // MyAnimeList uses 0 for series still airing; a banner's total has to cover the episodes
+    // already watched, and one more keeps an unfinished series from counting as completed
+    let total_episodes = match series_episodes {
+        0 if status == WatchStatus::Completed => watched_episodes,
+        0 => watched_episodes + 1,
+        series_episodes => series_episodes.max(watched_episodes),
+    };
+
+    let mal_id = match field("series_animedb_id").parse::<i64>() {
+        Ok(0) | Err(_) => None,
+        Ok(mal_id) => Some(mal_id),
+    };
+
+    let start_date = match field("my_start_date") {
+        "" | NO_DATE => today,
+        date => parse_date("my_start_date", date).unwrap_or(today),
+    };
+    let release_day = Weekday::ALL[start_date.weekday().number_days_from_sunday() as usize];
+
+    Ok(Banner {
+        banner_id: 0,
+        image_binary: vec![],
+        image_hash: None,
+        title,
+        alternate_titles: String::new(),
+        notes: field("my_comments").to_owned(),
+        release_day: release_day.as_str().to_owned(),
+        release_time: String::from(IMPORTED_RELEASE_TIME),
+        time_zone: String::from(DEFAULT_TIME_ZONE),
+        current_episodes: watched_episodes,
+        total_episodes,
+        status,
+        mal_id,
+        version: 0,
+    })
+}
+
+// newer exports use names, older ones the numeric codes
+fn parse_status(status: &str) -> Result<WatchStatus, RepoError> {
+    match status {
+        "Watching" | "1" => Ok(WatchStatus::Watching),
+        "Completed" | "2" => Ok(WatchStatus::Completed),
+        "On-Hold" | "3" => Ok(WatchStatus::OnHold),
+        "Dropped" | "4" => Ok(WatchStatus::Dropped),
+        "Plan to Watch" | "6" => Ok(WatchStatus::PlanToWatch),
+        status => Err(RepoError::validation(
+            "my_status",
+            format!("{status:?} is not a MyAnimeList status"),
+        )),
+    }
+}
+
+fn status_name(status: WatchStatus) -> &'static str {
+    match status {
+        WatchStatus::Watching => "Watching",
+        WatchStatus::Completed => "Completed",
+        WatchStatus::OnHold => "On-Hold",
+        WatchStatus::Dropped => "Dropped",
+        WatchStatus::PlanToWatch => "Plan to Watch",
+    }
+}
+
+// a CDATA section cannot contain "]]>", so it is split across two sections
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use time::{format_description::well_known::Iso8601, Weekday as DayOfWeek};
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<myanimelist>
+    <myinfo><user_name>bobross</user_name></myinfo>
+    <anime>
+        <series_animedb_id>52991</series_animedb_id>
+        <series_title><![CDATA[Sousou no Frieren]]></series_title>
+        <series_episodes>28</series_episodes>
+        <my_watched_episodes>3</my_watched_episodes>
+        <my_start_date>2026-10-02</my_start_date>
+        <my_status>Watching</my_status>
+        <my_comments><![CDATA[elves & mimics]]></my_comments>
+    </anime>
+    <anime>
+        <series_animedb_id>0</series_animedb_id>
+        <series_title>One Piece</series_title>
+        <series_episodes>0</series_episodes>
+        <my_watched_episodes>1100</my_watched_episodes>
+        <my_start_date>0000-00-00</my_start_date>
+        <my_status>1</my_status>
+    </anime>
+    <anime>
+        <series_title>Unknown</series_title>
+        <my_status>Rewatching</my_status>
+    </anime>
+</myanimelist>
+"#;
+
+    fn today() -> Date {
+        Date::parse("2026-10-18", &Iso8601::DATE).unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn summary(parsed: &[Result<Banner, ImportIssue>]) -> Vec<String> {
+        parsed
+            .iter()
+            .map(|entry| match entry {
+                Ok(banner) => serde_json::to_string(banner).unwrap(),
+                Err(issue) => serde_json::to_string(issue).unwrap(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn statuses_are_read_by_name_and_by_code() {
+        for (names, status) in [
+            (["Watching", "1"], WatchStatus::Watching),
+            (["Completed", "2"], WatchStatus::Completed),
+            (["On-Hold", "3"], WatchStatus::OnHold),
+            (["Dropped", "4"], WatchStatus::Dropped),
+            (["Plan to Watch", "6"], WatchStatus::PlanToWatch),
+        ] {
+            for name in names {
+                assert_eq!(parse_status(name).unwrap(), status);
+            }
+            assert_eq!(parse_status(status_name(status)).unwrap(), status);
+        }
+
+        for name in ["5", "watching", "", "Rewatching"] {
+            assert!(matches!(
+                parse_status(name),
+                Err(RepoError::Validation { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn gzipped_and_plain_exports_read_the_same() {
+        let plain = parse_mal_export(EXPORT.as_bytes(), today()).unwrap();
+        let gzipped = parse_mal_export(&gzip(EXPORT.as_bytes()), today()).unwrap();
+        assert_eq!(plain.len(), 3);
+        assert_eq!(summary(&plain), summary(&gzipped));
+
+        let frieren = plain[0].as_ref().ok().unwrap();
+        assert_eq!(frieren.title, "Sousou no Frieren");
+        assert_eq!(frieren.notes, "elves & mimics");
+        assert_eq!(frieren.mal_id, Some(52991));
+        assert_eq!((frieren.current_episodes, frieren.total_episodes), (3, 28));
+        // 2026-10-02 was a Friday
+        assert_eq!(frieren.release_day, "Friday");
+
+        // still airing, so one episode more than watched, on the day of the import
+        let one_piece = plain[1].as_ref().ok().unwrap();
+        assert_eq!(one_piece.mal_id, None);
+        assert_eq!(
+            (one_piece.current_episodes, one_piece.total_episodes),
+            (1100, 1101)
+        );
+        assert_eq!(today().weekday(), DayOfWeek::Sunday);
+        assert_eq!(one_piece.release_day, "Sunday");
+
+        let Err(unknown) = &plain[2] else {
+            panic!("an unknown status was imported");
+        };
+        assert_eq!((unknown.row, unknown.title.as_str()), (3, "Unknown"));
+    }
+
+    #[test]
+    fn broken_archives_and_other_files_are_rejected() {
+        let mut truncated = gzip(EXPORT.as_bytes());
+        truncated.truncate(truncated.len() / 2);
+
+        for data in [truncated, b"<html></html>".to_vec(), b"not xml <".to_vec()] {
+            assert!(matches!(
+                parse_mal_export(&data, today()),
+                Err(RepoError::Validation { .. })
+            ));
+        }
+    }
+}
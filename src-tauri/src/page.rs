@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, FromRow, QueryBuilder, Row, Sqlite, TypeInfo, ValueRef};
+use time::OffsetDateTime;
+
+use crate::repo_error::RepoError;
+use crate::validation::validate_page_size;
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total_count: i64,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        }
+    }
+
+    fn reversed(&self) -> Self {
+        match self {
+            SortDirection::Ascending => SortDirection::Descending,
+            SortDirection::Descending => SortDirection::Ascending,
+        }
+    }
+
+    // compares a column to a value that comes before it in this direction
+    fn past(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => " > ",
+            SortDirection::Descending => " < ",
+        }
+    }
+}
+
+// the value of one sort column, as SQLite stored it; a column computed from an
+// invalid value, such as a misspelt release day, is NULL
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum SortValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+impl SortValue {
+    fn from_row(row: &SqliteRow, column: &str) -> Result<Self, sqlx::Error> {
+        let value = row.try_get_raw(column)?;
+        if value.is_null() {
+            return Ok(SortValue::Null);
+        }
+
+        match value.type_info().name() {
+            "INTEGER" => row.try_get(column).map(SortValue::Integer),
+            "REAL" => row.try_get(column).map(SortValue::Real),
+            _ => row.try_get(column).map(SortValue::Text),
+        }
+    }
+
+    fn push_bind<'a>(&self, query: &mut QueryBuilder<'a, Sqlite>) {
+        match self {
+            SortValue::Null => query.push_bind(None::<i64>),
+            SortValue::Integer(value) => query.push_bind(*value),
+            SortValue::Real(value) => query.push_bind(*value),
+            SortValue::Text(value) => query.push_bind(value.clone()),
+        };
+    }
+
+    // SQLite orders NULL before every other value, so a NULL column is only past a
+    // value when reading descending, and only non-NULL ones are past a NULL ascending
+    fn push_past<'a>(
+        &self,
+        query: &mut QueryBuilder<'a, Sqlite>,
+        column: &str,
+        direction: SortDirection,
+    ) {
+        match (self, direction) {
+            (SortValue::Null, SortDirection::Ascending) => {
+                query.push(format!("{column} IS NOT NULL"));
+            }
+            (SortValue::Null, SortDirection::Descending) => {
+                query.push("0");
+            }
+            (_, SortDirection::Ascending) => {
+                query.push(format!("{column} > "));
+                self.push_bind(query);
+            }
+            (_, SortDirection::Descending) => {
+                query.push(format!("({column} < "));
+                self.push_bind(query);
+                query.push(format!(" OR {column} IS NULL)"));
+            }
+        }
+    }
+}
+
+// every listing is ordered by its sort columns and then banner_id, so a cursor
+// remembers those values of the row it was taken from and which way to read
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cursor {
+    pub sort_values: Vec<SortValue>,
+    pub banner_id: i64,
+    pub backward: bool,
+    // when the first page was read, in unix seconds; listings ordered relative to
+    // the current time use it so that later pages keep the same order
+    pub taken_at: i64,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, RepoError> {
+        let invalid = || RepoError::validation("cursor", "is not a valid cursor");
+
+        if !cursor.is_ascii() || !cursor.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
+
+pub struct PageRequest {
+    pub page_size: usize,
+    pub cursor: Option<Cursor>,
+    pub taken_at: OffsetDateTime,
+}
+
+impl PageRequest {
+    pub fn new(page_size: usize, cursor: Option<String>) -> Result<Self, RepoError> {
+        validate_page_size(page_size)?;
+
+        let cursor = cursor.as_deref().map(Cursor::decode).transpose()?;
+        let taken_at = match &cursor {
+            Some(cursor) => OffsetDateTime::from_unix_timestamp(cursor.taken_at)
+                .map_err(|_| RepoError::validation("cursor", "is not a valid cursor"))?,
+            None => OffsetDateTime::now_utc(),
+        };
+
+        Ok(PageRequest {
+            page_size,
+            cursor,
+            taken_at,
+        })
+    }
+
+    fn backward(&self) -> bool {
+        self.cursor.as_ref().is_some_and(|cursor| cursor.backward)
+    }
+
+    // rows arrive in reading order and hold one extra row when more remain past the page
+    fn finish<T>(&self, mut rows: Vec<(Vec<SortValue>, i64, T)>, total_count: i64) -> Page<T> {
+        let has_more = rows.len() > self.page_size;
+        rows.truncate(self.page_size);
+
+        let (has_prev, has_next) = if self.backward() {
+            rows.reverse();
+            (has_more, true)
+        } else {
+            (self.cursor.is_some(), has_more)
+        };
+
+        let cursor_at = |row: Option<&(Vec<SortValue>, i64, T)>, backward: bool| {
+            row.map(|(sort_values, banner_id, _)| {
+                Cursor {
+                    sort_values: sort_values.clone(),
+                    banner_id: *banner_id,
+                    backward,
+                    taken_at: self.taken_at.unix_timestamp(),
+                }
+                .encode()
+            })
+        };
+
+        let next_cursor = has_next.then(|| cursor_at(rows.last(), false)).flatten();
+        let prev_cursor = has_prev.then(|| cursor_at(rows.first(), true)).flatten();
+
+        Page {
+            items: rows.into_iter().map(|(_, _, item)| item).collect(),
+            total_count,
+            next_cursor,
+            prev_cursor,
+        }
+    }
+}
+
+fn sort_column(index: usize) -> String {
+    format!("sort_{index}")
+}
+
+// `listing` pushes a SELECT whose rows carry banner_id and, for every entry of
+// `order`, a sort_0, sort_1, ... column; rows are read in that order, then by banner_id
+pub async fn fetch_page<'a, T>(
+    database: &sqlx::Pool<Sqlite>,
+    request: &PageRequest,
+    order: &[SortDirection],
+    listing: impl Fn(&mut QueryBuilder<'a, Sqlite>),
+) -> Result<Page<T>, RepoError>
+where
+    T: for<'r> FromRow<'r, SqliteRow>,
+{
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM (");
+    listing(&mut count);
+    count.push(")");
+    let (total_count,): (i64,) = count.build_query_as().fetch_one(database).await?;
+
+    let mut query = QueryBuilder::new("SELECT * FROM (");
+    listing(&mut query);
+    query.push(") ");
+
+    let reading = |direction: SortDirection| {
+        if request.backward() {
+            direction.reversed()
+        } else {
+            direction
+        }
+    };
+
+    // for sort columns (a, b) the rows past the cursor are
+    // a > ? OR (a IS ? AND (b > ? OR (b IS ? AND banner_id > ?))), flipped for descending
+    // columns; IS also matches a NULL cursor value
+    if let Some(cursor) = &request.cursor {
+        if cursor.sort_values.len() != order.len() {
+            return Err(RepoError::validation(
+                "cursor",
+                "does not belong to this listing",
+            ));
+        }
+
+        query.push("WHERE ");
+        for (index, (direction, value)) in order.iter().zip(&cursor.sort_values).enumerate() {
+            let column = sort_column(index);
+
+            query.push("(");
+            value.push_past(&mut query, &column, reading(*direction));
+            query.push(format!(" OR ({column} IS "));
+            value.push_bind(&mut query);
+            query.push(" AND ");
+        }
+
+        query
+            .push(format!(
+                "banner_id{}",
+                reading(SortDirection::Ascending).past()
+            ))
+            .push_bind(cursor.banner_id)
+            .push("))".repeat(order.len()))
+            .push(" ");
+    }
+
+    query.push("ORDER BY ");
+    for (index, direction) in order.iter().enumerate() {
+        query.push(format!(
+            "{} {}, ",
+            sort_column(index),
+            reading(*direction).keyword()
+        ));
+    }
+    query
+        .push("banner_id ")
+        .push(reading(SortDirection::Ascending).keyword())
+        .push(" LIMIT ")
+        .push_bind(request.page_size as i64 + 1);
+
+    let rows = query
+        .build()
+        .fetch_all(database)
+        .await?
+        .iter()
+        .map(|row| {
+            let sort_values = (0..order.len())
+                .map(|index| SortValue::from_row(row, &sort_column(index)))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok((sort_values, row.try_get("banner_id")?, T::from_row(row)?))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+    Ok(request.finish(rows, total_count))
+}
+
+// the same paging for listings that are ranked in memory rather than in SQL,
+// by one ascending sort value; the items are re-ranked on every page, so a cursor
+// resumes after its (sort value, banner_id) even when the scores have moved
+pub fn paginate<T>(
+    mut items: Vec<(f64, i64, T)>,
+    request: &PageRequest,
+) -> Result<Page<T>, RepoError> {
+    let total_count = items.len() as i64;
+    items.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let position = match &request.cursor {
+        Some(cursor) => match cursor.sort_values.as_slice() {
+            [SortValue::Real(sort_value)] => Some((*sort_value, cursor.banner_id)),
+            [SortValue::Integer(sort_value)] => Some((*sort_value as f64, cursor.banner_id)),
+            _ => {
+                return Err(RepoError::validation(
+                    "cursor",
+                    "does not belong to this listing",
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let rows = match position {
+        Some(position) if request.backward() => items
+            .into_iter()
+            .rev()
+            .filter(|(sort_value, banner_id, _)| (*sort_value, *banner_id) < position)
+            .take(request.page_size + 1)
+            .collect::<Vec<_>>(),
+        Some(position) => items
+            .into_iter()
+            .filter(|(sort_value, banner_id, _)| (*sort_value, *banner_id) > position)
+            .take(request.page_size + 1)
+            .collect(),
+        None => items.into_iter().take(request.page_size + 1).collect(),
+    };
+
+    let rows = rows
+        .into_iter()
+        .map(|(sort_value, banner_id, item)| (vec![SortValue::Real(sort_value)], banner_id, item))
+        .collect();
+
+    Ok(request.finish(rows, total_count))
+}
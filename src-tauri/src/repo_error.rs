@@ -0,0 +1,62 @@
+use serde::Serialize;
+use std::fmt;
+
+// SQLITE_CONSTRAINT_UNIQUE and SQLITE_CONSTRAINT_PRIMARYKEY
+const DUPLICATE_CODES: [&str; 2] = ["2067", "1555"];
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind")]
+pub enum RepoError {
+    NotFound,
+    Duplicate,
+    Unauthorized,
+    Validation { field: String, reason: String },
+    Database { message: String },
+}
+
+impl RepoError {
+    pub fn validation(field: &str, reason: impl Into<String>) -> Self {
+        RepoError::Validation {
+            field: field.to_owned(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn database(message: impl Into<String>) -> Self {
+        RepoError::Database {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<sqlx::Error> for RepoError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = error {
+            return RepoError::NotFound;
+        }
+
+        if let Some(db_error) = error.as_database_error() {
+            let code = db_error.code().map(|c| c.to_string()).unwrap_or_default();
+
+            if DUPLICATE_CODES.contains(&code.as_str()) {
+                return RepoError::Duplicate;
+            }
+        }
+
+        RepoError::database(error.to_string())
+    }
+}
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::NotFound => write!(f, "not found"),
+            RepoError::Duplicate => write!(f, "already exists"),
+            RepoError::Unauthorized => write!(f, "unauthorized"),
+            RepoError::Validation { field, reason } => write!(f, "invalid {field}: {reason}"),
+            RepoError::Database { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {}
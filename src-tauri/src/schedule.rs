@@ -0,0 +1,438 @@
+use serde::Serialize;
+use sqlx::{QueryBuilder, Sqlite};
+use std::collections::BTreeMap;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use time_tz::{Offset, OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, TimeZone, Tz};
+
+use crate::banner::Banner;
+use crate::repo_error::RepoError;
+use crate::validation::{parse_release_time, parse_time_zone, Weekday};
+
+// broadcast schedules are usually published in JST, which has no daylight saving
+pub const DEFAULT_TIME_ZONE: &str = "Asia/Tokyo";
+
+const MINUTES_PER_WEEK: i64 = 7 * 24 * 60;
+
+// the minute of the week a banner airs at in its own time zone, counted from Sunday 00:00
+const LOCAL_RELEASE_MINUTE: &str = r#"
+    (
+        (CASE release_day
+            WHEN 'Sunday' THEN 0
+            WHEN 'Monday' THEN 1
+            WHEN 'Tuesday' THEN 2
+            WHEN 'Wednesday' THEN 3
+            WHEN 'Thursday' THEN 4
+            WHEN 'Friday' THEN 5
+            WHEN 'Saturday' THEN 6
+            END
+        ) * 1440
+        + CAST(substr(release_time, 1, 2) AS INTEGER) * 60
+        + CAST(substr(release_time, 4, 2) AS INTEGER)
+    )"#;
+
+// what a release-day order needs in SQL: the minute of the week it counts from, and
+// the UTC offset each time zone of the listing had then; a daylight saving change
+// within the coming week is not taken into account
+pub struct AiringOrder {
+    anchor_minute: i64,
+    offsets: Vec<(String, i64)>,
+}
+
+impl AiringOrder {
+    pub fn new(anchor: OffsetDateTime, time_zones: &[String]) -> Self {
+        let utc_anchor = anchor.to_offset(UtcOffset::UTC);
+        let anchor_minute = i64::from(utc_anchor.weekday().number_days_from_sunday()) * 1440
+            + i64::from(utc_anchor.hour()) * 60
+            + i64::from(utc_anchor.minute());
+
+        let offsets = time_zones
+            .iter()
+            .filter_map(|name| {
+                let offset = parse_time_zone(name).ok()?.get_offset_utc(&anchor).to_utc();
+                Some((name.clone(), i64::from(offset.whole_minutes())))
+            })
+            .collect();
+
+        AiringOrder {
+            anchor_minute,
+            offsets,
+        }
+    }
+
+    // pushes the minutes from the anchor until each banner's release minute in UTC,
+    // so that the banner airing next comes first; the key does not move as time
+    // passes, so listings can page by it
+    pub fn push_minutes_until_airing(&self, builder: &mut QueryBuilder<'_, Sqlite>) {
+        builder
+            .push("((")
+            .push(LOCAL_RELEASE_MINUTE)
+            .push(" - (CASE time_zone");
+        for (name, offset) in &self.offsets {
+            builder
+                .push(" WHEN ")
+                .push_bind(name.clone())
+                .push(" THEN ")
+                .push_bind(*offset);
+        }
+        builder
+            .push(" ELSE 0 END) - ")
+            .push_bind(self.anchor_minute)
+            .push(format!(" + 2 * {MINUTES_PER_WEEK}) % {MINUTES_PER_WEEK})"));
+    }
+}
+
+#[derive(Serialize)]
+pub struct NextAiring {
+    pub banner_id: i64,
+    pub next_airing: String,
+}
+
+#[derive(Serialize)]
+pub struct ScheduledEpisode {
+    pub banner_id: i64,
+    pub title: String,
+    pub episode: u32,
+    pub airs_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ScheduleSlot {
+    pub time: String,
+    pub episodes: Vec<ScheduledEpisode>,
+}
+
+#[derive(Serialize)]
+pub struct ScheduleDay {
+    pub weekday: Weekday,
+    pub date: String,
+    pub slots: Vec<ScheduleSlot>,
+}
+
+#[derive(Serialize)]
+pub struct WeeklySchedule {
+    pub week_start: String,
+    pub time_zone: String,
+    pub days: Vec<ScheduleDay>,
+    // titles of banners whose release day, time or time zone cannot be read
+    pub skipped: Vec<String>,
+}
+
+// a local time skipped by a daylight saving change airs once the clocks have moved
+// on, and a repeated one airs the first time round
+pub fn assume_time_zone(local: PrimitiveDateTime, time_zone: &Tz) -> OffsetDateTime {
+    match local.assume_timezone(time_zone) {
+        OffsetResult::Some(date_time) | OffsetResult::Ambiguous(date_time, _) => date_time,
+        OffsetResult::None => assume_time_zone(local + Duration::HOUR, time_zone),
+    }
+}
+
+// the banner's release time on `date` in its own time zone, with that day's offset
+fn airing_on(date: Date, release_time: Time, time_zone: &Tz) -> OffsetDateTime {
+    assume_time_zone(PrimitiveDateTime::new(date, release_time), time_zone)
+}
+
+pub fn next_airing(banner: &Banner, now: OffsetDateTime) -> Result<OffsetDateTime, RepoError> {
+    let release_day: Weekday = banner.release_day.parse()?;
+    let (hours, minutes) = parse_release_time(&banner.release_time)?;
+    let time_zone = parse_time_zone(&banner.time_zone)?;
+
+    let release_time = Time::from_hms(hours, minutes, 0)
+        .map_err(|e| RepoError::validation("release_time", e.to_string()))?;
+
+    let local_now = now.to_timezone(time_zone);
+    let days_ahead = (i64::from(release_day.days_from_sunday())
+        - i64::from(local_now.weekday().number_days_from_sunday()))
+    .rem_euclid(7);
+
+    let date = local_now.date() + Duration::days(days_ahead);
+    let mut airing = airing_on(date, release_time, time_zone);
+
+    if airing < now {
+        airing = airing_on(date + Duration::weeks(1), release_time, time_zone);
+    }
+
+    Ok(airing.to_offset(UtcOffset::UTC))
+}
+
+// the airing of episode 1, counted back one week per watched episode from the next
+// airing; it stays put while the banner keeps up with its broadcast
+pub fn first_airing(banner: &Banner, now: OffsetDateTime) -> Result<OffsetDateTime, RepoError> {
+    let time_zone = parse_time_zone(&banner.time_zone)?;
+    let next = next_airing(banner, now)?.to_timezone(time_zone);
+    let date = next.date() - Duration::weeks(i64::from(banner.current_episodes));
+
+    Ok(airing_on(date, next.time(), time_zone).to_offset(UtcOffset::UTC))
+}
+
+// lays out the seven days from week_start in the viewer's time zone; a banner is
+// assumed to air one episode a week, the first of them being the episode
+// after current_episodes at its next airing from now, and one that cannot be
+// placed is listed in skipped rather than failing the whole week
+pub fn weekly_schedule(
+    banners: &[Banner],
+    week_start: Date,
+    time_zone: &Tz,
+    now: OffsetDateTime,
+) -> Result<WeeklySchedule, RepoError> {
+    let start = airing_on(week_start, Time::MIDNIGHT, time_zone);
+    let end = airing_on(week_start + Duration::days(7), Time::MIDNIGHT, time_zone);
+
+    let mut days: Vec<BTreeMap<(u8, u8), Vec<ScheduledEpisode>>> =
+        (0..7).map(|_| BTreeMap::new()).collect();
+    let mut skipped = vec![];
+
+    for banner in banners {
+        let (airing, episode) = match airing_in_week(banner, start, end, now) {
+            Ok(Some(airing)) => airing,
+            Ok(None) => continue,
+            Err(_) => {
+                skipped.push(banner.title.clone());
+                continue;
+            }
+        };
+
+        if episode < 1 || (banner.total_episodes > 0 && episode > i64::from(banner.total_episodes))
+        {
+            continue;
+        }
+
+        let local_airing = airing.to_timezone(time_zone);
+        let day = (local_airing.date() - week_start).whole_days() as usize;
+
+        days[day]
+            .entry((local_airing.hour(), local_airing.minute()))
+            .or_default()
+            .push(ScheduledEpisode {
+                banner_id: banner.banner_id,
+                title: banner.title.clone(),
+                episode: episode as u32,
+                airs_at: airing
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .unwrap(),
+            });
+    }
+
+    let days = days
+        .into_iter()
+        .enumerate()
+        .map(|(day, slots)| {
+            let date = week_start + Duration::days(day as i64);
+
+            ScheduleDay {
+                weekday: Weekday::ALL[date.weekday().number_days_from_sunday() as usize],
+                date: date.to_string(),
+                slots: slots
+                    .into_iter()
+                    .map(|((hours, minutes), mut episodes)| {
+                        episodes.sort_by(|a, b| a.title.cmp(&b.title));
+
+                        ScheduleSlot {
+                            time: format!("{hours:02}:{minutes:02}"),
+                            episodes,
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(WeeklySchedule {
+        week_start: week_start.to_string(),
+        time_zone: time_zone.name().to_owned(),
+        days,
+        skipped,
+    })
+}
+
+// the banner's airing between start and end, if it has one, and which episode airs then
+fn airing_in_week(
+    banner: &Banner,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    now: OffsetDateTime,
+) -> Result<Option<(OffsetDateTime, i64)>, RepoError> {
+    let airing = next_airing(banner, start)?;
+    if airing >= end {
+        return Ok(None);
+    }
+
+    // both are the same weekday in the banner's time zone, whatever their offsets
+    let banner_zone = parse_time_zone(&banner.time_zone)?;
+    let weeks_from_now = (airing.to_timezone(banner_zone).date()
+        - next_airing(banner, now)?.to_timezone(banner_zone).date())
+    .whole_weeks();
+
+    Ok(Some((
+        airing,
+        i64::from(banner.current_episodes) + 1 + weeks_from_now,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::format_description::well_known::Rfc3339;
+
+    fn banner(release_day: &str, release_time: &str, time_zone: &str) -> Banner {
+        serde_json::from_value(json!({
+            "title": format!("{release_day} {release_time} {time_zone}"),
+            "release_day": release_day,
+            "release_time": release_time,
+            "time_zone": time_zone,
+            "current_episodes": 3,
+            "total_episodes": 12,
+        }))
+        .unwrap()
+    }
+
+    fn at(date_time: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(date_time, &Rfc3339).unwrap()
+    }
+
+    #[test]
+    fn next_airing_is_the_coming_release_in_the_banners_zone() {
+        let tokyo = banner("Monday", "12:00", "Asia/Tokyo");
+
+        // Sunday evening in UTC is already Monday morning in Tokyo
+        assert_eq!(
+            next_airing(&tokyo, at("2026-10-18T20:00:00Z")).unwrap(),
+            at("2026-10-19T03:00:00Z")
+        );
+        assert_eq!(
+            next_airing(&tokyo, at("2026-10-19T03:00:00Z")).unwrap(),
+            at("2026-10-19T03:00:00Z")
+        );
+        assert_eq!(
+            next_airing(&tokyo, at("2026-10-19T03:00:01Z")).unwrap(),
+            at("2026-10-26T03:00:00Z")
+        );
+    }
+
+    #[test]
+    fn next_airing_keeps_the_local_time_across_daylight_saving() {
+        let berlin = banner("Friday", "20:00", "Europe/Berlin");
+        assert_eq!(
+            next_airing(&berlin, at("2026-10-20T00:00:00Z")).unwrap(),
+            at("2026-10-23T18:00:00Z")
+        );
+        assert_eq!(
+            next_airing(&berlin, at("2026-10-24T00:00:00Z")).unwrap(),
+            at("2026-10-30T19:00:00Z")
+        );
+
+        // 02:30 is skipped when New York springs forward, so it airs an hour later
+        let new_york = banner("Sunday", "02:30", "America/New_York");
+        assert_eq!(
+            next_airing(&new_york, at("2026-03-07T12:00:00Z")).unwrap(),
+            at("2026-03-08T07:30:00Z")
+        );
+
+        // and 02:30 happens twice when Berlin falls back, so it airs the first time
+        let repeated = banner("Sunday", "02:30", "Europe/Berlin");
+        assert_eq!(
+            next_airing(&repeated, at("2026-10-24T12:00:00Z")).unwrap(),
+            at("2026-10-25T00:30:00Z")
+        );
+    }
+
+    #[test]
+    fn next_airing_rejects_unreadable_banners() {
+        let now = at("2026-10-18T00:00:00Z");
+
+        assert!(next_airing(&banner("Someday", "12:00", "Asia/Tokyo"), now).is_err());
+        assert!(next_airing(&banner("Monday", "noon", "Asia/Tokyo"), now).is_err());
+        assert!(next_airing(&banner("Monday", "12:00", ""), now).is_err());
+    }
+
+    fn week(banners: &[Banner], week_start: &str, now: OffsetDateTime) -> WeeklySchedule {
+        let week_start = crate::validation::parse_date("week_start", week_start).unwrap();
+        weekly_schedule(banners, week_start, parse_time_zone("UTC").unwrap(), now).unwrap()
+    }
+
+    // (date, time, title, episode) for every episode of the week
+    fn episodes(schedule: &WeeklySchedule) -> Vec<(&str, &str, &str, u32)> {
+        schedule
+            .days
+            .iter()
+            .flat_map(|day| {
+                day.slots.iter().flat_map(move |slot| {
+                    slot.episodes.iter().map(move |episode| {
+                        (
+                            day.date.as_str(),
+                            slot.time.as_str(),
+                            episode.title.as_str(),
+                            episode.episode,
+                        )
+                    })
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weekly_schedules_count_episodes_from_the_next_airing() {
+        let mut tokyo = banner("Monday", "12:00", "Asia/Tokyo");
+        tokyo.title = String::from("Tokyo");
+        tokyo.current_episodes = 3;
+        tokyo.total_episodes = 5;
+        let now = at("2026-10-18T00:00:00Z");
+
+        let numbered = |week_start| {
+            episodes(&week(std::slice::from_ref(&tokyo), week_start, now))
+                .iter()
+                .map(|(date, time, _, episode)| (date.to_string(), time.to_string(), *episode))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            numbered("2026-10-19"),
+            [("2026-10-19".into(), "03:00".into(), 4)]
+        );
+        assert_eq!(
+            numbered("2026-10-26"),
+            [("2026-10-26".into(), "03:00".into(), 5)]
+        );
+        // past the last episode
+        assert!(numbered("2026-11-02").is_empty());
+        // earlier weeks count back to the first episode
+        assert_eq!(
+            numbered("2026-10-12"),
+            [("2026-10-12".into(), "03:00".into(), 3)]
+        );
+        assert_eq!(
+            numbered("2026-09-28"),
+            [("2026-09-28".into(), "03:00".into(), 1)]
+        );
+        assert!(numbered("2026-09-21").is_empty());
+    }
+
+    #[test]
+    fn weekly_schedules_share_slots_and_skip_unreadable_banners() {
+        let mut frieren = banner("Friday", "23:00", "UTC");
+        frieren.title = String::from("Frieren");
+        frieren.total_episodes = 0;
+        let mut bleach = banner("Friday", "23:00", "UTC");
+        bleach.title = String::from("Bleach");
+        let mut unreadable = banner("Someday", "23:00", "UTC");
+        unreadable.title = String::from("Unreadable");
+
+        let schedule = week(
+            &[frieren, bleach, unreadable],
+            "2026-10-19",
+            at("2026-10-19T00:00:00Z"),
+        );
+
+        let friday = &schedule.days[4];
+        assert_eq!(friday.weekday, crate::validation::Weekday::Friday);
+        assert_eq!(friday.slots.len(), 1);
+        assert_eq!(
+            episodes(&schedule),
+            [
+                ("2026-10-23", "23:00", "Bleach", 4),
+                ("2026-10-23", "23:00", "Frieren", 4),
+            ]
+        );
+        assert_eq!(schedule.skipped, ["Unreadable"]);
+    }
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashSet;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::banner::Banner;
+
+pub const SNIPPET_START: &str = "<mark>";
+pub const SNIPPET_END: &str = "</mark>";
+pub const SNIPPET_ELLIPSIS: &str = "…";
+pub const SNIPPET_TOKENS: i64 = 12;
+
+// bm25 weights for the title, alternate_titles and notes columns
+pub const TITLE_WEIGHT: f64 = 10.0;
+pub const ALTERNATE_TITLES_WEIGHT: f64 = 5.0;
+pub const NOTES_WEIGHT: f64 = 1.0;
+
+// banners scoring below this are left out of fuzzy results
+pub const FUZZY_THRESHOLD: f64 = 0.6;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum SearchMode {
+    #[default]
+    FullText,
+    Fuzzy,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub banner: Banner,
+    pub snippet: String,
+}
+
+// every word is quoted so FTS5 syntax typed by the user is matched literally,
+// and the trailing * turns each word into a prefix query
+pub fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+
+    if terms.is_empty() {
+        return None;
+    }
+
+    Some(terms.join(" "))
+}
+
+// folds case, diacritics and macrons ("Shōnen" -> "shonen") and turns punctuation into spaces
+pub fn normalize_for_search(text: &str) -> String {
+    let folded: String = text
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn trigrams(text: &str) -> HashSet<String> {
+    text.split_whitespace()
+        .flat_map(|word| {
+            let padded: Vec<char> = format!("  {word} ").chars().collect();
+            padded
+                .windows(3)
+                .map(|window| window.iter().collect())
+                .collect::<Vec<String>>()
+        })
+        .collect()
+}
+
+fn trigram_similarity(a: &str, b: &str) -> f64 {
+    let a = trigrams(a);
+    let b = trigrams(b);
+    let union = a.union(&b).count();
+
+    if union == 0 {
+        return 0.0;
+    }
+
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+// each query word is matched against its closest word in the candidate, so a
+// misspelled fragment of a long title still scores well
+fn word_similarity(query: &str, candidate: &str) -> f64 {
+    let words: Vec<&str> = query.split_whitespace().collect();
+
+    if words.is_empty() {
+        return 0.0;
+    }
+
+    let total: f64 = words
+        .iter()
+        .map(|word| {
+            candidate
+                .split_whitespace()
+                .map(|other| strsim::normalized_damerau_levenshtein(word, other))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+
+    total / words.len() as f64
+}
+
+// both arguments must already be normalized
+pub fn fuzzy_score(query: &str, candidate: &str) -> f64 {
+    trigram_similarity(query, candidate).max(word_similarity(query, candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_expressions_quote_every_word_as_a_prefix() {
+        assert_eq!(
+            match_expression("  attack  on\ttitan ").as_deref(),
+            Some(r#""attack"* "on"* "titan"*"#)
+        );
+        assert_eq!(match_expression(""), None);
+        assert_eq!(match_expression(" \n "), None);
+    }
+
+    #[test]
+    fn match_expressions_keep_fts_syntax_literal() {
+        // quotes are doubled, and operators and column filters stay inside the quotes
+        assert_eq!(
+            match_expression(r#"say "hi""#).as_deref(),
+            Some(r#""say"* """hi"""*"#)
+        );
+        assert_eq!(
+            match_expression("NOT title:x* OR (a").as_deref(),
+            Some(r#""NOT"* "title:x*"* "OR"* "(a"*"#)
+        );
+    }
+
+    #[test]
+    fn search_text_folds_case_marks_and_punctuation() {
+        assert_eq!(normalize_for_search("Shōnen"), "shonen");
+        assert_eq!(normalize_for_search("Pokémon: Ｘ & Ｙ!"), "pokemon x y");
+        assert_eq!(
+            normalize_for_search("  Re:ZERO\n-Starting Life- "),
+            "re zero starting life"
+        );
+        assert_eq!(normalize_for_search("進撃の巨人"), "進撃の巨人");
+        assert_eq!(normalize_for_search("?!"), "");
+    }
+
+    #[test]
+    fn fuzzy_scores_forgive_typos_and_partial_titles() {
+        let score = |query: &str, candidate: &str| {
+            fuzzy_score(
+                &normalize_for_search(query),
+                &normalize_for_search(candidate),
+            )
+        };
+
+        assert_eq!(score("frieren", "Frieren"), 1.0);
+        assert!(score("freiren", "Frieren") >= FUZZY_THRESHOLD);
+        assert!(score("titan", "Attack on Titan") >= FUZZY_THRESHOLD);
+        assert!(score("shingeki", "Shingeki no Kyojin") > score("shingeki", "Attack on Titan"));
+        assert!(score("bleach", "Mushishi") < FUZZY_THRESHOLD);
+        assert_eq!(score("", "Frieren"), 0.0);
+    }
+}
@@ -0,0 +1,12 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+pub const SESSION_DURATION: time::Duration = time::Duration::days(7);
+
+const TOKEN_BYTES: usize = 32;
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
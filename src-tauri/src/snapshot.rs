@@ -0,0 +1,284 @@
+use serde::Serialize;
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Sqlite, SqliteConnection};
+use std::path::{Path, PathBuf};
+use std::{fs, time::Duration};
+use time::OffsetDateTime;
+
+use crate::repo_error::RepoError;
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const SNAPSHOT_EXTENSION: &str = ".db";
+
+// rolling back copies every table except these; sessions stay so nobody is logged out
+// mid-request, and the search index is rebuilt from the restored banners instead
+const SKIPPED_TABLES: [&str; 2] = ["_sqlx_migrations", "Sessions"];
+const SEARCH_INDEX_PREFIX: &str = "BannerSearch";
+
+#[derive(Clone)]
+pub struct SnapshotConfig {
+    pub dir: PathBuf,
+    // None turns scheduled snapshots off, rolling back still works
+    pub interval: Option<Duration>,
+    pub keep: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+    // rows are copied column for column, so only snapshots taken at the current schema
+    // version can be restored; older ones stay listed until rotation removes them
+    pub compatible: bool,
+}
+
+pub async fn take_snapshot(
+    db: &sqlx::Pool<Sqlite>,
+    config: &SnapshotConfig,
+) -> Result<SnapshotInfo, RepoError> {
+    let snapshot = write_snapshot(db, config).await?;
+    rotate_snapshots(config)?;
+
+    Ok(snapshot)
+}
+
+// VACUUM INTO writes a consistent copy while other connections keep working
+pub async fn write_snapshot(
+    db: &sqlx::Pool<Sqlite>,
+    config: &SnapshotConfig,
+) -> Result<SnapshotInfo, RepoError> {
+    fs::create_dir_all(&config.dir).map_err(|e| RepoError::database(e.to_string()))?;
+
+    let path = config.dir.join(snapshot_name(OffsetDateTime::now_utc()));
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().into_owned())
+        .execute(db)
+        .await?;
+
+    snapshot_info(&path, true)
+}
+
+// newest first
+pub async fn list_snapshots(
+    db: &sqlx::Pool<Sqlite>,
+    config: &SnapshotConfig,
+) -> Result<Vec<SnapshotInfo>, RepoError> {
+    let current = schema_version(&mut *db.acquire().await?).await?;
+
+    let mut snapshots = vec![];
+    for name in snapshot_names(config)? {
+        let path = config.dir.join(name);
+        // a snapshot that cannot be read could not be restored either
+        let version = match SqliteConnection::connect_with(
+            &SqliteConnectOptions::new().filename(&path).read_only(true),
+        )
+        .await
+        {
+            Ok(mut connection) => schema_version(&mut connection).await.ok().flatten(),
+            Err(_) => None,
+        };
+        snapshots.push(snapshot_info(
+            &path,
+            version.is_some() && version == current,
+        )?);
+    }
+
+    Ok(snapshots)
+}
+
+fn snapshot_names(config: &SnapshotConfig) -> Result<Vec<String>, RepoError> {
+    if !config.dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut names: Vec<String> = fs::read_dir(&config.dir)
+        .map_err(|e| RepoError::database(e.to_string()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| is_snapshot_name(name))
+        .collect();
+
+    // the timestamp in the name sorts in creation order
+    names.sort_unstable_by(|a, b| b.cmp(a));
+
+    Ok(names)
+}
+
+pub fn rotate_snapshots(config: &SnapshotConfig) -> Result<(), RepoError> {
+    for name in snapshot_names(config)?.iter().skip(config.keep) {
+        fs::remove_file(config.dir.join(name)).map_err(|e| RepoError::database(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+// the snapshot must come from the same schema version, since rows are copied column for column
+pub async fn restore_snapshot(
+    db: &sqlx::Pool<Sqlite>,
+    config: &SnapshotConfig,
+    name: &str,
+) -> Result<(), RepoError> {
+    if !is_snapshot_name(name) || !config.dir.join(name).is_file() {
+        return Err(RepoError::NotFound);
+    }
+    let path = config.dir.join(name);
+
+    let mut connection = db.acquire().await?;
+    sqlx::query("ATTACH DATABASE ? AS snapshot")
+        .bind(path.to_string_lossy().into_owned())
+        .execute(&mut *connection)
+        .await?;
+
+    // tables are emptied and refilled in any order, so keys are checked once at the end;
+    // the pragma only applies outside a transaction
+    let restored = match sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *connection)
+        .await
+    {
+        Ok(_) => copy_snapshot(&mut connection).await,
+        Err(error) => Err(error.into()),
+    };
+
+    // the connection goes back to the pool, so it must leave as it came whatever failed
+    let enabled = sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *connection)
+        .await;
+    let detached = sqlx::query("DETACH DATABASE snapshot")
+        .execute(&mut *connection)
+        .await;
+
+    // one that could not be reset is closed instead
+    if enabled.is_err() || detached.is_err() {
+        drop(connection.detach());
+    }
+
+    restored?;
+    enabled?;
+    detached?;
+    Ok(())
+}
+
+async fn schema_version(connection: &mut SqliteConnection) -> Result<Option<i64>, RepoError> {
+    sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations")
+        .fetch_one(connection)
+        .await
+        .map_err(RepoError::from)
+}
+
+async fn copy_snapshot(connection: &mut SqliteConnection) -> Result<(), RepoError> {
+    let current: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM main._sqlx_migrations")
+        .fetch_one(&mut *connection)
+        .await?;
+    let snapshot: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM snapshot._sqlx_migrations")
+            .fetch_one(&mut *connection)
+            .await?;
+
+    if current != snapshot {
+        return Err(RepoError::validation(
+            "snapshot",
+            "was taken with a different database version",
+        ));
+    }
+
+    let tables: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT name FROM main.sqlite_master
+        WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(&mut *connection)
+    .await?;
+    let tables: Vec<String> = tables
+        .into_iter()
+        .filter(|table| {
+            !SKIPPED_TABLES.contains(&table.as_str()) && !table.starts_with(SEARCH_INDEX_PREFIX)
+        })
+        .collect();
+
+    let mut transaction = connection.begin().await?;
+
+    // table names come from sqlite_master, never from the caller
+    for table in &tables {
+        sqlx::query(&format!("DELETE FROM main.\"{table}\""))
+            .execute(&mut transaction)
+            .await?;
+    }
+
+    for table in &tables {
+        sqlx::query(&format!(
+            "INSERT INTO main.\"{table}\" SELECT * FROM snapshot.\"{table}\""
+        ))
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    sqlx::query("DELETE FROM Sessions WHERE user_name NOT IN (SELECT user_name FROM Users)")
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query("INSERT INTO BannerSearch(BannerSearch) VALUES ('rebuild')")
+        .execute(&mut transaction)
+        .await?;
+
+    let violations = sqlx::query("PRAGMA foreign_key_check")
+        .fetch_all(&mut transaction)
+        .await?;
+    if !violations.is_empty() {
+        return Err(RepoError::validation(
+            "snapshot",
+            "has rows that reference missing rows",
+        ));
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+// e.g. snapshot-20261018T053233.123Z.db, so names sort by time
+fn snapshot_name(now: OffsetDateTime) -> String {
+    format!(
+        "{SNAPSHOT_PREFIX}{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z{SNAPSHOT_EXTENSION}",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second(),
+        now.millisecond()
+    )
+}
+
+// names arrive from the frontend, so anything that could leave the directory is refused
+fn is_snapshot_name(name: &str) -> bool {
+    name.strip_prefix(SNAPSHOT_PREFIX)
+        .and_then(|rest| rest.strip_suffix(SNAPSHOT_EXTENSION))
+        .is_some_and(|stamp| {
+            !stamp.is_empty()
+                && stamp
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, 'T' | 'Z' | '.'))
+        })
+}
+
+fn snapshot_info(path: &Path, compatible: bool) -> Result<SnapshotInfo, RepoError> {
+    let metadata = fs::metadata(path).map_err(|e| RepoError::database(e.to_string()))?;
+    let created_at = metadata
+        .modified()
+        .map(OffsetDateTime::from)
+        .unwrap_or_else(|_| OffsetDateTime::now_utc());
+
+    Ok(SnapshotInfo {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        created_at: created_at
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap(),
+        size_bytes: metadata.len(),
+        compatible,
+    })
+}
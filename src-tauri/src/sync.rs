@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::banner::{Banner, BannerPatch, WatchStatus};
+use crate::repo_error::RepoError;
+
+// one sync carries at most this many queued edits
+pub const MAX_SYNC_CHANGES: usize = 1000;
+
+// the method and data of the command the edit would have called while online
+#[derive(Deserialize)]
+#[serde(tag = "method", content = "data", rename_all = "snake_case")]
+pub enum SyncOperation {
+    AddBanner {
+        banner: Banner,
+    },
+    #[serde(rename_all = "camelCase")]
+    DeleteBanner {
+        banner_id: i64,
+    },
+    #[serde(rename_all = "camelCase")]
+    RenameBanner {
+        banner_id: i64,
+        title: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SetBannerStatus {
+        banner_id: i64,
+        status: WatchStatus,
+    },
+    #[serde(rename_all = "camelCase")]
+    UpdateBanner {
+        banner_id: i64,
+        patch: BannerPatch,
+    },
+}
+
+// banners added offline carry a negative banner_id until the server assigns one,
+// so later edits in the same batch can refer to them
+#[derive(Deserialize)]
+pub struct SyncChange {
+    #[serde(flatten)]
+    pub operation: SyncOperation,
+    // when the edit was made on the client, in RFC 3339
+    pub timestamp: String,
+    // the version of the banner the edit was made against; without one the edit
+    // overwrites whatever the server has
+    #[serde(default)]
+    pub base_version: Option<i64>,
+}
+
+impl SyncChange {
+    pub fn parse_timestamp(&self) -> Result<OffsetDateTime, RepoError> {
+        OffsetDateTime::parse(
+            &self.timestamp,
+            &time::format_description::well_known::Rfc3339,
+        )
+        .map_err(|_| RepoError::validation("timestamp", "must be an RFC 3339 date and time"))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "outcome")]
+pub enum SyncOutcome {
+    // version is None once the banner is deleted
+    Applied {
+        banner_id: i64,
+        version: Option<i64>,
+    },
+    // the banner changed on the server since base_version, so the edit was skipped;
+    // sending it again with the current version overwrites the server
+    Conflict {
+        banner: Banner,
+    },
+    Failed {
+        error: RepoError,
+    },
+}
+
+// outcomes line up with the submitted changes, banners are the user's state afterwards
+#[derive(Serialize)]
+pub struct SyncReport {
+    pub outcomes: Vec<SyncOutcome>,
+    pub banners: Vec<Banner>,
+}
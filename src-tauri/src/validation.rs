@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use time::{format_description::well_known::Iso8601, Date};
+use time_tz::{timezones, Tz};
+
+use crate::banner::{Banner, BannerPatch};
+use crate::image_store::image_content_type;
+use crate::repo_error::RepoError;
+
+pub const MAX_TITLE_LENGTH: usize = 200;
+pub const MAX_ALTERNATE_TITLES_LENGTH: usize = 1000;
+pub const MAX_NOTES_LENGTH: usize = 10_000;
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+pub const MAX_IMPORT_BYTES: usize = 20 * 1024 * 1024;
+pub const MAX_PAGE_SIZE: usize = 100;
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Sunday,
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Weekday::Sunday => "Sunday",
+            Weekday::Monday => "Monday",
+            Weekday::Tuesday => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday => "Thursday",
+            Weekday::Friday => "Friday",
+            Weekday::Saturday => "Saturday",
+        }
+    }
+
+    pub fn days_from_sunday(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl FromStr for Weekday {
+    type Err = RepoError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Weekday::ALL
+            .into_iter()
+            .find(|day| day.as_str() == value)
+            .ok_or_else(|| {
+                RepoError::validation("release_day", format!("{value:?} is not a weekday"))
+            })
+    }
+}
+
+pub fn normalize_title(title: &str) -> Result<String, RepoError> {
+    let title = title.trim();
+
+    if title.is_empty() {
+        return Err(RepoError::validation("title", "must not be empty"));
+    }
+
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(RepoError::validation(
+            "title",
+            format!("must be at most {MAX_TITLE_LENGTH} characters"),
+        ));
+    }
+
+    Ok(title.to_owned())
+}
+
+pub fn validate_length(field: &str, value: &str, max_length: usize) -> Result<(), RepoError> {
+    if value.chars().count() > max_length {
+        return Err(RepoError::validation(
+            field,
+            format!("must be at most {max_length} characters"),
+        ));
+    }
+
+    Ok(())
+}
+
+// tags are compared case-insensitively, so they are stored trimmed, lowercased and deduplicated
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, RepoError> {
+    let tags: BTreeSet<String> = tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+
+    if tags.len() > MAX_TAGS {
+        return Err(RepoError::validation(
+            "tags",
+            format!("must have at most {MAX_TAGS} tags"),
+        ));
+    }
+
+    for tag in &tags {
+        if tag.is_empty() {
+            return Err(RepoError::validation("tags", "must not be empty"));
+        }
+
+        validate_length("tags", tag, MAX_TAG_LENGTH)?;
+    }
+
+    Ok(tags.into_iter().collect())
+}
+
+pub fn validate_page_size(page_size: usize) -> Result<(), RepoError> {
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        return Err(RepoError::validation(
+            "page_size",
+            format!("must be between 1 and {MAX_PAGE_SIZE}"),
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn parse_release_time(release_time: &str) -> Result<(u8, u8), RepoError> {
+    let invalid = || RepoError::validation("release_time", "must be a HH:MM time");
+
+    let (hours, minutes) = release_time.split_once(':').ok_or_else(invalid)?;
+    // parse alone would also take a sign, as in "+1"
+    let two_digits = |part: &str| part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit());
+    if !two_digits(hours) || !two_digits(minutes) {
+        return Err(invalid());
+    }
+
+    let hours: u8 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u8 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+
+    Ok((hours, minutes))
+}
+
+// a source timezone is stored as its IANA name, e.g. "Asia/Tokyo"
+pub fn parse_time_zone(time_zone: &str) -> Result<&'static Tz, RepoError> {
+    timezones::get_by_name(time_zone).ok_or_else(|| {
+        RepoError::validation("time_zone", "must be an IANA time zone such as Asia/Tokyo")
+    })
+}
+
+pub fn parse_date(field: &str, date: &str) -> Result<Date, RepoError> {
+    Date::parse(date, &Iso8601::DATE)
+        .map_err(|_| RepoError::validation(field, "must be a YYYY-MM-DD date"))
+}
+
+pub fn validate_episodes(current_episodes: u32, total_episodes: u32) -> Result<(), RepoError> {
+    if current_episodes > total_episodes {
+        return Err(RepoError::validation(
+            "current_episodes",
+            "must not exceed total_episodes",
+        ));
+    }
+
+    Ok(())
+}
+
+// an empty image means the banner has no cover
+pub fn validate_image(image_binary: &[u8]) -> Result<(), RepoError> {
+    if image_binary.is_empty() {
+        return Ok(());
+    }
+
+    if image_binary.len() > MAX_IMAGE_BYTES {
+        return Err(RepoError::validation(
+            "image_binary",
+            format!("must be at most {MAX_IMAGE_BYTES} bytes"),
+        ));
+    }
+
+    if image_content_type(image_binary).is_none() {
+        return Err(RepoError::validation(
+            "image_binary",
+            "must be a PNG, JPEG or WebP image",
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn validate_banner(banner: &Banner) -> Result<(), RepoError> {
+    normalize_title(&banner.title)?;
+    validate_length(
+        "alternate_titles",
+        &banner.alternate_titles,
+        MAX_ALTERNATE_TITLES_LENGTH,
+    )?;
+    validate_length("notes", &banner.notes, MAX_NOTES_LENGTH)?;
+    banner.release_day.parse::<Weekday>()?;
+    parse_release_time(&banner.release_time)?;
+    parse_time_zone(&banner.time_zone)?;
+    validate_episodes(banner.current_episodes, banner.total_episodes)?;
+    validate_image(&banner.image_binary)
+}
+
+pub fn validate_patch(patch: &BannerPatch, banner: &Banner) -> Result<(), RepoError> {
+    if let Some(alternate_titles) = &patch.alternate_titles {
+        validate_length(
+            "alternate_titles",
+            alternate_titles,
+            MAX_ALTERNATE_TITLES_LENGTH,
+        )?;
+    }
+
+    if let Some(notes) = &patch.notes {
+        validate_length("notes", notes, MAX_NOTES_LENGTH)?;
+    }
+
+    if let Some(tags) = &patch.tags {
+        normalize_tags(tags)?;
+    }
+
+    if let Some(release_day) = &patch.release_day {
+        release_day.parse::<Weekday>()?;
+    }
+
+    if let Some(release_time) = &patch.release_time {
+        parse_release_time(release_time)?;
+    }
+
+    if let Some(time_zone) = &patch.time_zone {
+        parse_time_zone(time_zone)?;
+    }
+
+    validate_episodes(
+        patch.current_episodes.unwrap_or(banner.current_episodes),
+        patch.total_episodes.unwrap_or(banner.total_episodes),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected_field<T: std::fmt::Debug>(result: Result<T, RepoError>) -> String {
+        match result {
+            Err(RepoError::Validation { field, .. }) => field,
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn release_times_must_be_two_digit_hours_and_minutes() {
+        assert_eq!(parse_release_time("00:00").unwrap(), (0, 0));
+        assert_eq!(parse_release_time("09:05").unwrap(), (9, 5));
+        assert_eq!(parse_release_time("23:59").unwrap(), (23, 59));
+
+        for release_time in [
+            "24:00", "12:60", "9:30", "09:30:00", "0930", "ab:cd", "", "+1:30",
+        ] {
+            assert_eq!(
+                rejected_field(parse_release_time(release_time)),
+                "release_time",
+                "{release_time:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn titles_are_trimmed_and_limited_in_characters() {
+        assert_eq!(normalize_title("  Frieren \n").unwrap(), "Frieren");
+
+        assert_eq!(rejected_field(normalize_title("")), "title");
+        assert_eq!(rejected_field(normalize_title(" \t ")), "title");
+
+        // counted in characters, not bytes
+        let longest = "葬".repeat(MAX_TITLE_LENGTH);
+        assert_eq!(normalize_title(&longest).unwrap(), longest);
+        assert_eq!(
+            rejected_field(normalize_title(&format!("{longest}x"))),
+            "title"
+        );
+    }
+}
@@ -5,7 +5,11 @@ import {
   BannerService,
   Banner,
   BannerLocalMemory,
-  pageSize,
+  Page,
+  QueuedChange,
+  RepoError,
+  describeRepoError,
+  localTimeZone,
 } from "./services/bannerService";
 import "bootstrap/dist/css/bootstrap.min.css";
 import HomeView from "./components/HomeView";
@@ -69,20 +73,24 @@ function App() {
   const [searchText, setSearchText] = useState("");
   const [isLoading, setIsLoading] = useState(false);
   const [hasMore, setHasMore] = useState(true);
-  const [pageCount, setPageCount] = useState(0);
+  const [nextCursor, setNextCursor] = useState<string | undefined>();
   const isBottom = useHasScrolledToBottom();
   const isOnline = useNetworkStatus();
   const [usernames, setUserNames] = useState<string[]>([]);
   const bannerServiceRef = useRef<BannerService | BannerLocalMemory>(
     isOnline ? new BannerService() : new BannerLocalMemory()
   );
+  // kept while offline so the session survives until the network is back
+  const onlineServiceRef = useRef<BannerService | null>(null);
+  const [syncMessages, setSyncMessages] = useState<string[]>([]);
+  const [nextAirings, setNextAirings] = useState<Record<number, string>>({});
   const [userName, setUserName] = useState<string>("");
   const [password, setPassword] = useState<string>("");
 
   const resetBanners = async () => {
     if (bannerServiceRef.current instanceof BannerLocalMemory) return;
     setBanners([]);
-    setPageCount(0);
+    setNextCursor(undefined);
     setHasMore(true);
   };
 
@@ -101,18 +109,55 @@ function App() {
     await bannerServiceRef.current.simulate_attack();
   };
 
+  // outcomes line up with the queued changes, so each message can name its edit
+  const syncChanges = async (
+    remoteService: BannerService,
+    changeLog: QueuedChange[]
+  ) => {
+    if (changeLog.length === 0) return;
+    try {
+      const report = await remoteService.syncServer(changeLog);
+      setBanners(report.banners);
+      setNextCursor(undefined);
+      setHasMore(false);
+      setSyncMessages(
+        report.outcomes.flatMap((outcome, index) => {
+          switch (outcome.outcome) {
+            case "Applied":
+              return [];
+            case "Conflict":
+              return [
+                `"${outcome.banner.title}" changed on another device, your offline ${changeLog[index].method} was not applied`,
+              ];
+            case "Failed":
+              return [
+                `Your offline ${changeLog[index].method} failed: ${describeRepoError(outcome.error)}`,
+              ];
+          }
+        })
+      );
+    } catch (error) {
+      const reason = (error as RepoError)?.kind
+        ? describeRepoError(error as RepoError)
+        : String(error);
+      setSyncMessages([`Your offline changes could not be synced: ${reason}`]);
+    }
+  };
+
   useEffect(() => {
     if (isOnline) {
       if (bannerServiceRef.current instanceof BannerLocalMemory) {
-        const remoteService = new BannerService();
-        const change_log = bannerServiceRef.current.getChangeLog();
-        remoteService.syncServer(change_log);
+        const remoteService = onlineServiceRef.current ?? new BannerService();
+        const changeLog = bannerServiceRef.current.getChangeLog();
         bannerServiceRef.current = remoteService;
+        onlineServiceRef.current = null;
+        syncChanges(remoteService, changeLog);
       }
     } else {
       if (bannerServiceRef.current instanceof BannerService) {
-        const localService = new BannerLocalMemory();
+        const localService = new BannerLocalMemory(bannerServiceRef.current);
         localService.cacheBanners(banners);
+        onlineServiceRef.current = bannerServiceRef.current;
         bannerServiceRef.current = localService;
       }
     }
@@ -130,37 +175,47 @@ function App() {
     }
   }, [isBottom, currentView]);
 
+  useEffect(() => {
+    if (banners.length === 0) return;
+    bannerServiceRef.current.getNextAirings().then((airings) => {
+      setNextAirings(
+        Object.fromEntries(airings.map((a) => [a.banner_id, a.next_airing]))
+      );
+    });
+  }, [banners]);
+
   const reloadBanners = async (bannerCount: number) => {
-    const updatedPageCount = Math.ceil(bannerCount / pageSize);
-    setPageCount(updatedPageCount);
     setBanners([]);
-    for (let i = 0; i < updatedPageCount; i++) {
-      const newBanners = await bannerServiceRef.current.getPagedBanners(i);
-      setBanners((prev) => [...prev, ...newBanners]);
-    }
+    let reloaded: Banner[] = [];
+    let cursor: string | undefined;
+    do {
+      const page = await bannerServiceRef.current.getPagedBanners(cursor);
+      reloaded = [...reloaded, ...page.items];
+      cursor = page.next_cursor ?? undefined;
+    } while (cursor && reloaded.length < bannerCount);
+    setBanners(reloaded);
+    setNextCursor(cursor);
+    setHasMore(cursor !== undefined);
   };
 
   const loadBanners = async () => {
     if (isLoading || !hasMore) return;
     setIsLoading(true);
 
-    let newBanners: Banner[];
+    let page: Page<Banner>;
 
     if (searchText.trim() === "") {
-      newBanners = await bannerServiceRef.current.getPagedBanners(pageCount);
+      page = await bannerServiceRef.current.getPagedBanners(nextCursor);
     } else {
-      newBanners = await bannerServiceRef.current.searchBanners(
+      page = await bannerServiceRef.current.searchBanners(
         searchText,
-        pageCount
+        nextCursor
       );
     }
 
-    if (newBanners.length === 0) {
-      setHasMore(false);
-    } else {
-      setPageCount(pageCount + 1);
-      setBanners((prev) => [...prev, ...newBanners]);
-    }
+    setBanners((prev) => [...prev, ...page.items]);
+    setNextCursor(page.next_cursor ?? undefined);
+    setHasMore(page.next_cursor !== null);
     setIsLoading(false);
   };
 
@@ -182,12 +237,17 @@ function App() {
   const handleAddBanner = async () => {
     const bytes = await blobToBinary(imageFile!);
     await bannerServiceRef.current.addBanner({
+      banner_id: 0,
       image_binary: bytes,
       title: title,
+      alternate_titles: "",
+      notes: "",
       release_day: releaseDay,
       release_time: releaseTime,
+      time_zone: localTimeZone(),
       current_episodes: currentEpisodes,
       total_episodes: totalEpisodes,
+      status: "Watching",
     });
     setTitle("");
     setReleaseDay("");
@@ -212,15 +272,18 @@ function App() {
     setCurrentView(newView);
   };
 
+  const getBannerImageUrl = (bannerId: number, thumbnail?: boolean) =>
+    bannerServiceRef.current.getBannerImageUrl(bannerId, thumbnail);
+
   const handleDeleteBanner = (banner: Banner) => {
     if (window.confirm("Are you sure you want to delete this banner?")) {
-      bannerServiceRef.current.deleteBanner(banner.title);
+      bannerServiceRef.current.deleteBanner(banner.banner_id);
       reloadBanners(banners.length - 1);
     }
   };
 
   const handleUpdateCurrentEpisodes = async (
-    id: string,
+    id: number,
     current_episodes: number
   ) => {
     bannerServiceRef.current.updateCurrentEpisodes(id, current_episodes);
@@ -228,25 +291,25 @@ function App() {
   };
 
   const handleUpdateTotalEpisodes = async (
-    id: string,
+    id: number,
     total_episodes: number
   ) => {
     bannerServiceRef.current.updateTotalEpisodes(id, total_episodes);
     reloadBanners(banners.length);
   };
 
-  const handleUpdateReleaseDay = async (id: string, release_day: string) => {
+  const handleUpdateReleaseDay = async (id: number, release_day: string) => {
     bannerServiceRef.current.updateReleaseDay(id, release_day);
     reloadBanners(banners.length);
   };
 
-  const handleUpdateReleaseTime = async (id: string, release_time: string) => {
+  const handleUpdateReleaseTime = async (id: number, release_time: string) => {
     bannerServiceRef.current.updateReleaseTime(id, release_time);
     reloadBanners(banners.length);
   };
 
   const handleSearch = (e: React.SetStateAction<string>) => {
-    setPageCount(0);
+    setNextCursor(undefined);
     setSearchText(e);
   };
 
@@ -265,6 +328,21 @@ function App() {
       <h1 className="text-center mb-4">
         Track Anime {isOnline ? "" : "(Offline Mode)"}
       </h1>
+      {syncMessages.length > 0 && (
+        <div className="alert alert-warning" role="alert">
+          <ul className="mb-2">
+            {syncMessages.map((message, index) => (
+              <li key={index}>{message}</li>
+            ))}
+          </ul>
+          <button
+            className="btn btn-sm btn-outline-secondary"
+            onClick={() => setSyncMessages([])}
+          >
+            Dismiss
+          </button>
+        </div>
+      )}
       {currentView === "login" && (
         <LoginScreen
           userName={userName}
@@ -303,6 +381,8 @@ function App() {
           handleViewChange={handleViewChange}
           searchText={searchText}
           banners={banners}
+          nextAirings={nextAirings}
+          getBannerImageUrl={getBannerImageUrl}
           searchTextChange={handleSearch}
         ></ViewView>
       )}
@@ -314,6 +394,8 @@ function App() {
           handleDeleteBanner={handleDeleteBanner}
           searchText={searchText}
           banners={banners}
+          nextAirings={nextAirings}
+          getBannerImageUrl={getBannerImageUrl}
           handleUpdateCurrentEpisodes={handleUpdateCurrentEpisodes}
           handleUpdateReleaseDay={handleUpdateReleaseDay}
           handleUpdateReleaseTime={handleUpdateReleaseTime}
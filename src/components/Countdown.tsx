@@ -1,38 +1,19 @@
 import { useState, useEffect } from "react";
 
 interface Props {
-    targetDay: string;
-    targetTime: string;
+    // the next airing as an ISO 8601 instant, computed by the backend
+    target: string;
 }
 
-function CountdownToDay({ targetDay, targetTime }: Props) {
+function CountdownToDay({ target }: Props) {
     const [timeLeft, setTimeLeft] = useState<string>('');
 
     useEffect(() => {
         const calculateTimeLeft = () => {
             const now = new Date();
-            const target = new Date();
+            const targetDate = new Date(target);
 
-            // Set the target day
-            const days = ['Sunday', 'Monday', 'Tuesday', 'Wednesday', 'Thursday', 'Friday', 'Saturday'];
-            const targetDayIndex = days.indexOf(targetDay);
-            const currentDayIndex = now.getDay();
-
-            // Calculate days until target
-            let daysUntilTarget = targetDayIndex - currentDayIndex;
-            if (daysUntilTarget < 0) daysUntilTarget += 7;
-
-            // Set the target time
-            const [hours, minutes] = targetTime.split(':').map(Number);
-            target.setDate(now.getDate() + daysUntilTarget);
-            target.setHours(hours, minutes, 0, 0);
-
-            // If we've passed the target time today, add 7 days
-            if (daysUntilTarget === 0 && now > target) {
-                target.setDate(target.getDate() + 7);
-            }
-
-            const difference = target.getTime() - now.getTime();
+            const difference = targetDate.getTime() - now.getTime();
 
             if (difference > 0) {
                 const days = Math.floor(difference / (1000 * 60 * 60 * 24));
@@ -50,7 +31,7 @@ function CountdownToDay({ targetDay, targetTime }: Props) {
         const timer = setInterval(calculateTimeLeft, 1000);
 
         return () => clearInterval(timer);
-    }, [targetDay, targetTime]);
+    }, [target]);
 
     return <span className="text-white">{timeLeft}</span>;
 }
@@ -1,64 +1,48 @@
 import { useState, useEffect } from "react";
 import CountdownToDay from "./Countdown";
 
+// search snippets wrap each match in <mark>...</mark>; split on the markers
+// instead of injecting HTML so user text is never interpreted as markup
+const renderSnippet = (snippet: string) =>
+  snippet.split("<mark>").map((part, index) => {
+    const [match, rest] = part.includes("</mark>")
+      ? part.split("</mark>", 2)
+      : ["", part];
+    return (
+      <span key={index}>
+        {match && <mark>{match}</mark>}
+        {rest}
+      </span>
+    );
+  });
+
 interface Props {
-  imageFile: Blob;
+  imageUrl: string | null;
   title: string;
-  releaseDay: string;
-  releaseTime: string;
+  snippet?: string;
+  nextAiring?: string;
   currentEpisodes: number;
   totalEpisodes: number;
 }
 
 function InfoBanner({
-  imageFile,
+  imageUrl,
   title,
-  releaseDay,
-  releaseTime,
+  snippet,
+  nextAiring,
   currentEpisodes,
   totalEpisodes,
 }: Props) {
-  const [imageUrl, setImageUrl] = useState("");
   const [urgencyLevel, setUrgencyLevel] = useState<"high" | "medium" | "low">(
     "low"
   );
 
-  useEffect(() => {
-    if (imageFile) {
-      const url = URL.createObjectURL(imageFile);
-      setImageUrl(url);
-
-      return () => URL.revokeObjectURL(url);
-    }
-  }, [imageFile]);
-
   useEffect(() => {
     const calculateUrgency = () => {
-      const now = new Date();
-      const target = new Date();
+      if (!nextAiring) return;
 
-      const days = [
-        "Sunday",
-        "Monday",
-        "Tuesday",
-        "Wednesday",
-        "Thursday",
-        "Friday",
-        "Saturday",
-      ];
-      const targetDayIndex = days.indexOf(releaseDay);
-      const currentDayIndex = now.getDay();
-
-      let daysUntilTarget = targetDayIndex - currentDayIndex;
-      if (daysUntilTarget < 0) daysUntilTarget += 7;
-
-      const [hours, minutes] = releaseTime.split(":").map(Number);
-      target.setDate(now.getDate() + daysUntilTarget);
-      target.setHours(hours, minutes, 0, 0);
-
-      if (daysUntilTarget === 0 && now > target) {
-        target.setDate(target.getDate() + 7);
-      }
+      const now = new Date();
+      const target = new Date(nextAiring);
 
       const hoursUntilRelease =
         (target.getTime() - now.getTime()) / (1000 * 60 * 60);
@@ -76,7 +60,7 @@ function InfoBanner({
     const timer = setInterval(calculateUrgency, 1000 * 60);
 
     return () => clearInterval(timer);
-  }, [releaseDay, releaseTime]);
+  }, [nextAiring]);
 
   if (!imageUrl) return <p>No image selected</p>;
 
@@ -117,6 +101,7 @@ function InfoBanner({
         <img
           src={imageUrl}
           alt={title}
+          loading="lazy"
           className="w-100 h-100"
           style={{ objectFit: "cover", borderRadius: "inherit" }}
         />
@@ -139,6 +124,14 @@ function InfoBanner({
         >
           {title}
         </h5>
+        {snippet && (
+          <p
+            className="text-white small mb-2"
+            style={{ textShadow: "1px 1px 2px rgba(0,0,0,0.8)" }}
+          >
+            {renderSnippet(snippet)}
+          </p>
+        )}
         <div className="progress mb-2" style={{ height: "8px" }}>
           <div
             className="progress-bar bg-success"
@@ -169,7 +162,7 @@ function InfoBanner({
           {urgencyStyles[urgencyLevel].text}
         </div>
         <div style={{ textShadow: "1px 1px 2px rgba(0,0,0,0.8)" }}>
-          <CountdownToDay targetDay={releaseDay} targetTime={releaseTime} />
+          {nextAiring && <CountdownToDay target={nextAiring} />}
         </div>
       </div>
     </div>
@@ -1,6 +1,6 @@
 type LoginResult =
-  | { status: 'Admin' }
-  | { status: 'User' }
+  | { status: 'Admin'; token: string }
+  | { status: 'User'; token: string }
   | { status: 'Fail'; error: string };
 
   export default LoginResult;
\ No newline at end of file
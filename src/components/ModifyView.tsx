@@ -17,16 +17,18 @@ interface Props {
   searchText: string;
   searchTextChange: (value: React.SetStateAction<string>) => void;
   banners: Banner[];
+  nextAirings: Record<number, string>;
+  getBannerImageUrl: (bannerId: number, thumbnail?: boolean) => string;
   handleUpdateTotalEpisodes: (
-    id: string,
+    id: number,
     total_episodes: number
   ) => Promise<void>;
   handleUpdateCurrentEpisodes: (
-    id: string,
+    id: number,
     current_episodes: number
   ) => Promise<void>;
-  handleUpdateReleaseDay: (id: string, release_day: string) => Promise<void>;
-  handleUpdateReleaseTime: (id: string, release_time: string) => Promise<void>;
+  handleUpdateReleaseDay: (id: number, release_day: string) => Promise<void>;
+  handleUpdateReleaseTime: (id: number, release_time: string) => Promise<void>;
   handleDeleteBanner: (banner: Banner) => void;
 }
 
@@ -35,6 +37,8 @@ function ModifyView({
   searchText,
   searchTextChange,
   banners,
+  nextAirings,
+  getBannerImageUrl,
   handleUpdateTotalEpisodes,
   handleUpdateCurrentEpisodes,
   handleUpdateReleaseDay,
@@ -55,7 +59,7 @@ function ModifyView({
       <div className="mb-4">
         <input
           type="text"
-          placeholder="Search by title"
+          placeholder="Search titles and notes"
           value={searchText}
           onChange={(e) => searchTextChange(e.target.value)}
           className="form-control"
@@ -68,14 +72,12 @@ function ModifyView({
               <div className="row">
                 <div className="col-md-6">
                   <InfoBanner
-                    imageFile={
-                      new Blob([new Uint8Array(banner.image_binary)], {
-                        type: "image/png",
-                      })
+                    imageUrl={
+                      banner.image_hash ? getBannerImageUrl(banner.banner_id, true) : null
                     }
                     title={banner.title}
-                    releaseDay={banner.release_day}
-                    releaseTime={banner.release_time}
+                    snippet={banner.snippet}
+                    nextAiring={nextAirings[banner.banner_id]}
                     currentEpisodes={banner.current_episodes}
                     totalEpisodes={banner.total_episodes}
                   />
@@ -91,7 +93,7 @@ function ModifyView({
                         value={banner.current_episodes}
                         onChange={(e) =>
                           handleUpdateCurrentEpisodes(
-                            banner.title,
+                            banner.banner_id,
                             parseInt(e.target.value)
                           )
                         }
@@ -106,7 +108,7 @@ function ModifyView({
                         value={banner.total_episodes}
                         onChange={(e) =>
                           handleUpdateTotalEpisodes(
-                            banner.title,
+                            banner.banner_id,
                             parseInt(e.target.value)
                           )
                         }
@@ -118,7 +120,7 @@ function ModifyView({
                         className="form-select"
                         value={banner.release_day}
                         onChange={(e) =>
-                          handleUpdateReleaseDay(banner.title, e.target.value)
+                          handleUpdateReleaseDay(banner.banner_id, e.target.value)
                         }
                       >
                         {DAYS_OF_WEEK.map((day) => (
@@ -135,7 +137,7 @@ function ModifyView({
                         className="form-control"
                         value={banner.release_time}
                         onChange={(e) =>
-                          handleUpdateReleaseTime(banner.title, e.target.value)
+                          handleUpdateReleaseTime(banner.banner_id, e.target.value)
                         }
                       />
                     </div>
@@ -6,6 +6,8 @@ interface Props {
   handleViewChange: (item: View) => void;
   searchText: string;
   banners: Banner[];
+  nextAirings: Record<number, string>;
+  getBannerImageUrl: (bannerId: number, thumbnail?: boolean) => string;
   searchTextChange: (value: React.SetStateAction<string>) => void;
 }
 
@@ -13,6 +15,8 @@ function ViewView({
   handleViewChange,
   searchText,
   banners,
+  nextAirings,
+  getBannerImageUrl,
   searchTextChange,
 }: Props) {
   return (
@@ -29,7 +33,7 @@ function ViewView({
       <div className="mb-4">
         <input
           type="text"
-          placeholder="Search by title"
+          placeholder="Search titles and notes"
           value={searchText}
           onChange={(e) => searchTextChange(e.target.value)}
           className="form-control"
@@ -39,14 +43,12 @@ function ViewView({
         {banners.map((banner, index) => (
           <div key={index} className="col px-2">
             <InfoBanner
-              imageFile={
-                new Blob([new Uint8Array(banner.image_binary)], {
-                  type: "image/png",
-                })
+              imageUrl={
+                banner.image_hash ? getBannerImageUrl(banner.banner_id, true) : null
               }
               title={banner.title}
-              releaseDay={banner.release_day}
-              releaseTime={banner.release_time}
+              snippet={banner.snippet}
+              nextAiring={nextAirings[banner.banner_id]}
               currentEpisodes={banner.current_episodes}
               totalEpisodes={banner.total_episodes}
             />
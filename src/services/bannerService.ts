@@ -1,25 +1,218 @@
-import { invoke } from "@tauri-apps/api/core";
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import LoginResult from "../components/LoginResult";
 
 export interface Banner {
-    image_binary: number[];
+    banner_id: number;
+    image_binary?: number[];
+    image_hash: string | null;
     title: string;
+    alternate_titles: string;
+    notes: string;
     release_day: string;
     release_time: string;
+    time_zone: string;
     current_episodes: number;
     total_episodes: number;
+    status: WatchStatus;
+    mal_id?: number | null;
+    version?: number;
+    snippet?: string;
+}
+
+export type WatchStatus = 'Watching' | 'Completed' | 'OnHold' | 'Dropped' | 'PlanToWatch';
+
+export type SearchMode = 'FullText' | 'Fuzzy';
+
+export interface BannerPatch {
+    image_binary?: number[];
+    alternate_titles?: string;
+    notes?: string;
+    tags?: string[];
+    release_day?: string;
+    release_time?: string;
+    time_zone?: string;
+    current_episodes?: number;
+    total_episodes?: number;
+}
+
+export type RepoError =
+    | { kind: 'NotFound' }
+    | { kind: 'Duplicate' }
+    | { kind: 'Unauthorized' }
+    | { kind: 'Validation'; field: string; reason: string }
+    | { kind: 'Database'; message: string };
+
+// matches the backend's Display for RepoError
+export const describeRepoError = (error: RepoError): string => {
+    switch (error.kind) {
+        case 'NotFound':
+            return "not found";
+        case 'Duplicate':
+            return "already exists";
+        case 'Unauthorized':
+            return "unauthorized";
+        case 'Validation':
+            return `invalid ${error.field}: ${error.reason}`;
+        case 'Database':
+            return error.message;
+    }
+};
+
+export interface NextAiring {
+    banner_id: number;
+    next_airing: string;
+}
+
+export interface WeeklySchedule {
+    week_start: string;
+    time_zone: string;
+    days: {
+        weekday: string;
+        date: string;
+        slots: {
+            time: string;
+            episodes: { banner_id: number; title: string; episode: number; airs_at: string }[];
+        }[];
+    }[];
+    skipped: string[];
+}
+
+// the IANA name of the time zone the browser is in, such as "Europe/Berlin"
+export const localTimeZone = (): string => Intl.DateTimeFormat().resolvedOptions().timeZone;
+
+export interface IcsExport {
+    ics: string;
+    skipped: string[];
+}
+
+export interface MalExport {
+    xml: string;
+    skipped: string[];
+}
+
+export interface ImportReport {
+    dry_run: boolean;
+    inserted: string[];
+    updated: string[];
+    unchanged: string[];
+    conflicts: { row: number; title: string; banner_id: number }[];
+    invalid: { row: number; title: string; error: RepoError }[];
+}
+
+export type MergePolicy = 'KeepExisting' | 'PreferBackup';
+
+export interface RestoreReport {
+    users_added: number;
+    users_updated: number;
+    users_skipped: number;
+    banners_added: number;
+    banners_updated: number;
+    banners_skipped: number;
+    logs_added: number;
+}
+
+export interface SnapshotInfo {
+    name: string;
+    created_at: string;
+    size_bytes: number;
+    // false for snapshots taken before an upgrade, which cannot be restored
+    compatible: boolean;
+}
+
+// saves generated content through a temporary download link
+const downloadFile = (content: string | Uint8Array, fileName: string, type: string) => {
+    const url = URL.createObjectURL(new Blob([content], { type }));
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = fileName;
+    link.click();
+    URL.revokeObjectURL(url);
+};
+
+export type SortField = 'Title' | 'Progress' | 'EpisodesRemaining' | 'DateAdded' | 'ReleaseDay';
+
+export interface BannerQuery {
+    filter?: {
+        title?: string;
+        status?: WatchStatus;
+        release_days?: string[];
+        min_progress?: number;
+        max_progress?: number;
+        tags?: string[];
+    };
+    sort?: { field: SortField; direction?: 'Ascending' | 'Descending' }[];
+}
+
+export interface Page<T> {
+    items: T[];
+    total_count: number;
+    next_cursor: string | null;
+    prev_cursor: string | null;
 }
 
 export const pageSize = 20;
 
+// the offline cache pages by offset, so its cursors are just stringified offsets
+const pageOf = <T>(items: T[], cursor?: string): Page<T> => {
+    const start = cursor ? Number(cursor) : 0;
+    const end = start + pageSize;
+    return {
+        items: items.slice(start, end),
+        total_count: items.length,
+        next_cursor: end < items.length ? String(end) : null,
+        prev_cursor: start > 0 ? String(Math.max(start - pageSize, 0)) : null,
+    };
+};
+
+// an edit made offline, replayed by sync_changes once the network is back
+export interface QueuedChange {
+    method: string;
+    data: any;
+    timestamp: string;
+    base_version?: number;
+}
+
+export type SyncOutcome =
+    | { outcome: 'Applied'; banner_id: number; version: number | null }
+    | { outcome: 'Conflict'; banner: Banner }
+    | { outcome: 'Failed'; error: RepoError };
+
+export interface SyncReport {
+    outcomes: SyncOutcome[];
+    banners: Banner[];
+}
+
 export class BannerLocalMemory {
     public currentUser: string = "";
     public isAdmin: boolean = false;
     private banners: Banner[] = [];
-    private changes: { method: string; data: any }[] = [];
+    private changes: QueuedChange[] = [];
+    // banners added offline get negative ids until the server assigns real ones
+    private nextLocalId = -1;
+
+    // the service this one stands in for, whose session is kept if the user logged in before going offline
+    private onlineService?: BannerService;
+
+    constructor(onlineService?: BannerService) {
+        this.onlineService = onlineService;
+    }
 
     async simulate_attack() {
-        await invoke ("simulate_attack", {userName: "bobross"});
+        if (!this.onlineService) {
+            throw new Error("log in before simulating an attack");
+        }
+        await this.onlineService.simulate_attack();
+    }
+
+    // the base version is the one the cached banner had when the edit was made
+    private queueChange(method: string, data: any, banner?: Banner) {
+        this.changes.push({
+            method,
+            data,
+            timestamp: new Date().toISOString(),
+            base_version: banner?.version,
+        });
     }
 
     async login(_userName: string, _password: string): Promise<number> {
@@ -34,84 +227,98 @@ export class BannerLocalMemory {
         const initialLength = this.banners.length;
         const exists = this.banners.find(b => b.title === banner.title);
         if (!exists) {
-            this.banners.push(banner);
+            this.banners.push({ ...banner, banner_id: this.nextLocalId-- });
         }
         if (this.banners.length > initialLength) {
-            this.changes.push({
-                method: "add_banner",
-                data: { banner }
-            });
+            this.queueChange("add_banner", { banner: this.banners[this.banners.length - 1] });
         }
     }
 
-    async deleteBanner(title: string) {
-        const initialLength = this.banners.length;
-        this.banners = this.banners.filter(b => b.title !== title);
-        if (this.banners.length < initialLength) {
-            this.changes.push({
-                method: "delete_banner",
-                data: { title }
-            });
+    async deleteBanner(bannerId: number) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
+        this.banners = this.banners.filter(b => b.banner_id !== bannerId);
+        if (banner) {
+            this.queueChange("delete_banner", { bannerId }, banner);
+        }
+    }
+
+    async renameBanner(bannerId: number, title: string) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
+        if (banner && !this.banners.some(b => b.title === title)) {
+            banner.title = title;
+            this.queueChange("rename_banner", { bannerId, title }, banner);
         }
     }
 
-    async getPagedBanners(pageCount: number): Promise<Banner[]> {
-        const start = pageCount * pageSize;
-        return this.banners.slice(start, start + pageSize);
+    async getPagedBanners(cursor?: string, status?: WatchStatus): Promise<Page<Banner>> {
+        return pageOf(this.banners.filter(b => !status || b.status === status), cursor);
     }
 
-    async searchBanners(query: string, pageCount: number): Promise<Banner[]> {
-        const start = pageCount * pageSize;
-        const end = start + pageSize
+    async searchBanners(query: string, cursor?: string, status?: WatchStatus, _mode?: SearchMode): Promise<Page<Banner>> {
         const lowerQuery = query.toLowerCase();
-        return this.banners.filter(b => b.title.toLowerCase().includes(lowerQuery)).slice(start, end);
+        return pageOf(
+            this.banners
+                .filter(b => !status || b.status === status)
+                .filter(b => [b.title, b.alternate_titles, b.notes].some(text => text.toLowerCase().includes(lowerQuery))),
+            cursor
+        );
+    }
+
+    async setBannerStatus(bannerId: number, status: WatchStatus) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
+        if (banner) {
+            banner.status = status;
+            this.queueChange("set_banner_status", { bannerId, status }, banner);
+        }
     }
 
     async getAllBanners(): Promise<Banner[]> {
         return [...this.banners];
     }
 
-    async updateCurrentEpisodes(title: string, currentEpisodes: number) {
-        const banner = this.banners.find(b => b.title === title);
+    async getBannerImage(bannerId: number): Promise<number[]> {
+        return this.banners.find(b => b.banner_id === bannerId)?.image_binary ?? [];
+    }
+
+    // airings are computed by the backend, so there are none to show while offline
+    async getNextAirings(): Promise<NextAiring[]> {
+        return [];
+    }
+
+    getBannerImageUrl(bannerId: number, _thumbnail: boolean = false): string {
+        const image = this.banners.find(b => b.banner_id === bannerId)?.image_binary;
+        return image ? URL.createObjectURL(new Blob([new Uint8Array(image)])) : "";
+    }
+
+    async updateCurrentEpisodes(bannerId: number, currentEpisodes: number) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
         if (banner) {
             banner.current_episodes = currentEpisodes;
-            this.changes.push({
-                method: "update_banner_current_episodes",
-                data: { title, currentEpisodes }
-            });
+            this.queueChange("update_banner", { bannerId, patch: { current_episodes: currentEpisodes } }, banner);
         }
     }
 
-    async updateTotalEpisodes(title: string, totalEpisodes: number) {
-        const banner = this.banners.find(b => b.title === title);
+    async updateTotalEpisodes(bannerId: number, totalEpisodes: number) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
         if (banner) {
             banner.total_episodes = totalEpisodes;
-            this.changes.push({
-                method: "update_banner_total_episodes",
-                data: { title, totalEpisodes }
-            });
+            this.queueChange("update_banner", { bannerId, patch: { total_episodes: totalEpisodes } }, banner);
         }
     }
 
-    async updateReleaseDay(title: string, releaseDay: string) {
-        const banner = this.banners.find(b => b.title === title);
+    async updateReleaseDay(bannerId: number, releaseDay: string) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
         if (banner) {
             banner.release_day = releaseDay;
-            this.changes.push({
-                method: "update_banner_release_day",
-                data: { title, releaseDay }
-            });
+            this.queueChange("update_banner", { bannerId, patch: { release_day: releaseDay } }, banner);
         }
     }
 
-    async updateReleaseTime(title: string, releaseTime: string) {
-        const banner = this.banners.find(b => b.title === title);
+    async updateReleaseTime(bannerId: number, releaseTime: string) {
+        const banner = this.banners.find(b => b.banner_id === bannerId);
         if (banner) {
             banner.release_time = releaseTime;
-            this.changes.push({
-                method: "update_banner_release_time",
-                data: { title, releaseTime }
-            });
+            this.queueChange("update_banner", { bannerId, patch: { release_time: releaseTime } }, banner);
         }
     }
 
@@ -119,7 +326,7 @@ export class BannerLocalMemory {
         this.banners = [...banners];
     }
 
-    getChangeLog(): { method: string; data: any }[] {
+    getChangeLog(): QueuedChange[] {
         return [...this.changes];
     }
 }
@@ -127,20 +334,27 @@ export class BannerLocalMemory {
 export class BannerService {
     public currentUser: string = "";
     public isAdmin: boolean = false;
+    private sessionToken: string = "";
 
     async simulate_attack() {
-        await invoke ("simulate_attack", {userName: "bobross"});
+        await invoke ("simulate_attack", {token: this.sessionToken});
     }
 
     async login(userName: string, password: string): Promise<number> {
         const result = await invoke<LoginResult> ("login", {userName, password});
+        if (result.status !== 'Fail') {
+            // banners saved before time zones were stored take the zone the user is in
+            await invoke("fill_missing_time_zones", {timeZone: localTimeZone(), token: result.token});
+        }
         switch (result.status) {
             case 'Admin':
                 this.currentUser = userName;
+                this.sessionToken = result.token;
                 this.isAdmin = true;
                 return 0;
             case 'User':
                 this.currentUser = userName;
+                this.sessionToken = result.token;
                 this.isAdmin = false;
                 return 1;
             case 'Fail':
@@ -149,47 +363,166 @@ export class BannerService {
           }
     }
 
+    async logout() {
+        await invoke("logout", { token: this.sessionToken });
+        this.sessionToken = "";
+        this.currentUser = "";
+    }
+
     async register(userName: string, password: string): Promise<boolean> {
-        return await invoke("register_user", { userName, password, isAdmin: false});
+        try {
+            await invoke("register_user", { userName, password, isAdmin: false});
+            return true;
+        } catch (error) {
+            if ((error as RepoError).kind === 'Duplicate') {
+                return false;
+            }
+            throw error;
+        }
     }
 
     async addBanner(banner: Banner) {
-        await invoke ("add_banner", { banner, userName: this.currentUser });
+        await invoke ("add_banner", { banner, token: this.sessionToken });
     }
 
-    async deleteBanner(title: string) {
-        await invoke("delete_banner", { title, userName: this.currentUser  })
+    async deleteBanner(bannerId: number) {
+        await invoke("delete_banner", { bannerId, token: this.sessionToken })
     }
 
-    async getPagedBanners(pageCount: number): Promise<Banner[]> {
-        return await invoke("get_paged_banners", {pageSize, pageCount, userName: this.currentUser })
+    async renameBanner(bannerId: number, title: string) {
+        await invoke("rename_banner", { bannerId, title, token: this.sessionToken })
     }
 
-    async searchBanners(query: string, pageCount: number): Promise<Banner[]> {
-        return await invoke("search_banners", { query, pageSize, pageCount, userName: this.currentUser  })
+    async getPagedBanners(cursor?: string, status?: WatchStatus): Promise<Page<Banner>> {
+        return await invoke("get_paged_banners", { status, pageSize, cursor, token: this.sessionToken })
     }
 
-    async updateCurrentEpisodes(title: string, current_episodes: number) {
-        await invoke("update_banner_current_episodes", { title, currentEpisodes: current_episodes, userName: this.currentUser  });
+    async searchBanners(query: string, cursor?: string, status?: WatchStatus, mode?: SearchMode): Promise<Page<Banner>> {
+        return await invoke("search_banners", { query, mode, status, pageSize, cursor, token: this.sessionToken })
     }
 
-    async updateTotalEpisodes(title: string, total_episodes: number) {
-        await invoke("update_banner_total_episodes", { title, totalEpisodes: total_episodes, userName: this.currentUser  });
+    async getNextAirings(): Promise<NextAiring[]> {
+        return await invoke("next_airing", { token: this.sessionToken });
     }
 
-    async updateReleaseDay(title: string, release_day: string) {
-        await invoke("update_banner_release_day", { title, releaseDay: release_day, userName: this.currentUser });
+    async getWeeklySchedule(weekStart: string): Promise<WeeklySchedule> {
+        return await invoke("get_weekly_schedule", { weekStart, timeZone: localTimeZone(), token: this.sessionToken });
     }
 
-    async updateReleaseTime(title: string, release_time: string) {
-        await invoke("update_banner_release_time", { title, releaseTime: release_time, userName: this.currentUser });
+    async exportIcs(): Promise<string[]> {
+        const { ics, skipped }: IcsExport = await invoke("export_ics", { token: this.sessionToken });
+        downloadFile(ics, "anime-schedule.ics", "text/calendar");
+        return skipped;
     }
 
-    async syncServer (
-        changeLog: { method: string; data: any }[]
-    ) {
-        for (const change of changeLog) {
-            await invoke(change.method, change.data);
-        }
+    // accepts the .xml.gz file MyAnimeList exports as well as the extracted .xml
+    async importMal(file: Blob): Promise<ImportReport> {
+        const data = Array.from(new Uint8Array(await file.arrayBuffer()));
+        return await invoke("import_mal", { data, token: this.sessionToken });
+    }
+
+    // `mapping` renames the file's headers to banner columns, e.g. { "Name": "title" }
+    async importCsv(file: Blob, mapping?: Record<string, string>, dryRun: boolean = false): Promise<ImportReport> {
+        const data = Array.from(new Uint8Array(await file.arrayBuffer()));
+        return await invoke("import_csv", { data, mapping, dryRun, token: this.sessionToken });
+    }
+
+    async exportCsv() {
+        const csv: string = await invoke("export_csv", { token: this.sessionToken });
+        downloadFile(csv, "anime-list.csv", "text/csv");
+    }
+
+    // admin only; the archive holds every user's password hash. the backend writes it
+    // straight to the chosen file, false means the dialog was cancelled
+    async createBackup(): Promise<boolean> {
+        const date = new Date().toISOString().slice(0, 10);
+        const path = await save({
+            defaultPath: `our-anime-list-${date}.zip`,
+            filters: [{ name: "Backup", extensions: ["zip"] }],
+        });
+        if (!path) return false;
+        await invoke("create_backup", { path, token: this.sessionToken });
+        return true;
+    }
+
+    // null means the dialog was cancelled
+    async restoreBackup(policy: MergePolicy = "KeepExisting"): Promise<RestoreReport | null> {
+        const path = await open({ filters: [{ name: "Backup", extensions: ["zip"] }] });
+        if (!path) return null;
+        return await invoke("restore_backup", { path, policy, token: this.sessionToken });
+    }
+
+    async listSnapshots(): Promise<SnapshotInfo[]> {
+        return await invoke("list_snapshots", { token: this.sessionToken });
+    }
+
+    async createSnapshot(): Promise<SnapshotInfo> {
+        return await invoke("create_snapshot", { token: this.sessionToken });
+    }
+
+    // returns the snapshot taken of the current data just before rolling back
+    async restoreSnapshot(name: string): Promise<SnapshotInfo> {
+        return await invoke("restore_snapshot", { name, token: this.sessionToken });
+    }
+
+    // returns the titles left out of the file for having no MyAnimeList id
+    async exportMal(): Promise<string[]> {
+        const { xml, skipped }: MalExport = await invoke("export_mal", { token: this.sessionToken });
+        downloadFile(xml, "animelist.xml", "application/xml");
+        return skipped;
+    }
+
+    async queryBanners(query: BannerQuery, cursor?: string): Promise<Page<Banner>> {
+        return await invoke("query_banners", { query, pageSize, cursor, token: this.sessionToken })
+    }
+
+    async getBannerTags(bannerId: number): Promise<string[]> {
+        return await invoke("get_banner_tags", { bannerId, token: this.sessionToken });
+    }
+
+    async updateTags(bannerId: number, tags: string[]) {
+        await this.updateBanner(bannerId, { tags });
+    }
+
+    async getSortedBannersReleaseDay(cursor?: string): Promise<Page<Banner>> {
+        return await invoke("get_sorted_banners_release_day", { pageSize, cursor, token: this.sessionToken })
+    }
+
+    async getBannerImage(bannerId: number): Promise<number[]> {
+        return await invoke("get_banner_image", { bannerId, token: this.sessionToken });
+    }
+
+    getBannerImageUrl(bannerId: number, thumbnail: boolean = false): string {
+        const suffix = thumbnail ? "/thumbnail" : "";
+        return `${convertFileSrc(String(bannerId), "banner")}${suffix}?token=${this.sessionToken}`;
+    }
+
+    async setBannerStatus(bannerId: number, status: WatchStatus) {
+        await invoke("set_banner_status", { bannerId, status, token: this.sessionToken });
+    }
+
+    async updateBanner(bannerId: number, patch: BannerPatch) {
+        await invoke("update_banner", { bannerId, patch, token: this.sessionToken });
+    }
+
+    async updateCurrentEpisodes(bannerId: number, current_episodes: number) {
+        await this.updateBanner(bannerId, { current_episodes });
+    }
+
+    async updateTotalEpisodes(bannerId: number, total_episodes: number) {
+        await this.updateBanner(bannerId, { total_episodes });
+    }
+
+    async updateReleaseDay(bannerId: number, release_day: string) {
+        await this.updateBanner(bannerId, { release_day });
+    }
+
+    async updateReleaseTime(bannerId: number, release_time: string) {
+        await this.updateBanner(bannerId, { release_time });
+    }
+
+    // conflicting edits are skipped and come back with the server's banner; nothing is retried
+    async syncServer(changeLog: QueuedChange[]): Promise<SyncReport> {
+        return await invoke("sync_changes", { changes: changeLog, token: this.sessionToken });
     }
 } 
\ No newline at end of file